@@ -3,7 +3,8 @@ use std::path::PathBuf;
 use crate::helper_functions::iter_ffmpeg_events;
 #[cfg(feature = "hyperDebug")]
 use crate::helper_functions::parse_debug;
-use crate::frame_shape::FrameShape;
+use crate::frame_shape::{AudioOverrides, FrameShape};
+use crate::switches::AudioTracks;
 use ffmpeg_sidecar;
 use ffmpeg_sidecar::child::FfmpegChild;
 use ffmpeg_sidecar::command::FfmpegCommand;
@@ -50,7 +51,8 @@ fn concat_audio_streams(p_list: Vec<PathBuf>, temp: &PathBuf, stream_id: usize)
 }
 pub fn join_audio_video_streams(audio_segments: Vec<Vec<PathBuf>>,
                                 temp: &PathBuf, video_temp: &PathBuf, video_out: PathBuf,
-                                frame_shape: &FrameShape) -> PathBuf {
+                                frame_shape: &FrameShape, overrides: &AudioOverrides,
+                                tracks: AudioTracks) -> PathBuf {
     println!("Audio/Video joiner Started, this can be slow");
 
     #[cfg(feature = "hyperDebug")]
@@ -75,12 +77,16 @@ pub fn join_audio_video_streams(audio_segments: Vec<Vec<PathBuf>>,
     let mut worker = FfmpegCommand::new();
     worker.args(["-loglevel", "error"]);
     worker.input(video_temp.to_str().unwrap());
+    let groups = audio_items.len();
     for inp in audio_items {
         worker.input(inp.to_str().unwrap());
     }
-    worker.args(["-filter_complex", &*frame_shape.audio_args_with_vid()]);
+    if tracks.mix() {
+        worker.args(["-filter_complex", &*frame_shape.audio_args_with_vid(overrides)]);
+    }
     worker.args(["-c:v", "copy", ]).arg("-y").arg("-shortest");
-    worker.map("0:v:0").map("[d]");
+    worker.map("0:v:0");
+    worker.args(audio_track_args(tracks, groups, 1));
     worker.output(&video_out.to_str().unwrap());
 
     #[cfg(feature = "hyperDebug")]
@@ -93,3 +99,50 @@ pub fn join_audio_video_streams(audio_segments: Vec<Vec<PathBuf>>,
 
     video_out
 }
+
+/// `-map` and stream tags for the audio of the output, the mix is read from `[d]` and the stem of
+/// group n from input `first_stem + n`. the first stream is the default, every stream is titled
+pub(crate) fn audio_track_args(tracks: AudioTracks, groups: usize, first_stem: usize) -> Vec<String> {
+    let mut maps = vec![];
+    let mut titles = vec![];
+    if tracks.mix() {
+        maps.push("[d]".to_string());
+        titles.push("Mix".to_string());
+    }
+    if tracks.stems() {
+        for i in 0..groups {
+            maps.push(format!("{}:a:0", first_stem + i));
+            titles.push(format!("Tile {}", i + 1));
+        }
+    }
+
+    let mut args = vec![];
+    for map in maps {
+        args.extend(["-map".to_string(), map]);
+    }
+    for (k, title) in titles.into_iter().enumerate() {
+        args.extend([format!("-metadata:s:a:{}", k), format!("title={}", title)]);
+        let disposition = if k == 0 { "default" } else { "0" };
+        args.extend([format!("-disposition:a:{}", k), disposition.to_string()]);
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audio_tracks_mapped() {
+        assert_eq!(
+            audio_track_args(AudioTracks::Mix, 3, 1),
+            ["-map", "[d]", "-metadata:s:a:0", "title=Mix", "-disposition:a:0", "default"]
+        );
+        let both = audio_track_args(AudioTracks::Both, 2, 3).join(" ");
+        assert_eq!(both, "-map [d] -map 3:a:0 -map 4:a:0 \
+            -metadata:s:a:0 title=Mix -disposition:a:0 default \
+            -metadata:s:a:1 title=Tile 1 -disposition:a:1 0 \
+            -metadata:s:a:2 title=Tile 2 -disposition:a:2 0");
+        assert!(audio_track_args(AudioTracks::Stems, 2, 1).starts_with(&["-map".to_string(), "1:a:0".to_string()]));
+    }
+}
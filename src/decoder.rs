@@ -0,0 +1,205 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::ChildStdout;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+
+use ffmpeg_sidecar::child::FfmpegChild;
+use ffmpeg_sidecar::command::FfmpegCommand;
+use ffmpeg_sidecar::event::{FfmpegEvent, LogLevel, OutputVideoFrame};
+use ffmpeg_sidecar::iter::FfmpegIterator;
+
+use crate::encoder::{SAMPLE_BYTES, SAMPLE_RATE};
+#[cfg(feature = "hyperDebug")]
+use crate::helper_functions;
+
+/// frames decoded ahead of the joiner for each clip, a larger buffer smooths out slow
+/// clips at the cost of `FRAME_BUFFER` tile sized frames of memory per decoder
+const FRAME_BUFFER: usize = 8;
+
+/// a single clip being decoded on its own thread
+///
+/// frames are pushed into a bounded queue so ffmpeg only runs `FRAME_BUFFER` frames ahead,
+/// dropping the decoder stops the thread and kills ffmpeg
+pub(crate) struct Decoder {
+    frames: Receiver<OutputVideoFrame>,
+    /// used frame buffers handed back to the decoding thread, see [Decoder::recycle]
+    spare: SyncSender<Vec<u8>>,
+}
+
+impl Decoder {
+    /// start decoding, `ffm` must output `rgb24` raw video of exactly `size` to stdout
+    pub(crate) fn spawn(mut ffm: FfmpegCommand, src: PathBuf, size: (u32, u32), fps: f32) -> Decoder {
+        #[cfg(feature = "hyperDebug")]
+        ffm.print_command();
+        let child = ffm.spawn().unwrap();
+        let (tx, rx) = sync_channel(FRAME_BUFFER);
+        // every frame in flight can be returned without blocking the joiner
+        let (spare_tx, spare_rx) = sync_channel(FRAME_BUFFER + 2);
+        thread::Builder::new()
+            .name(format!("decode {}", src.file_name().unwrap_or_default().to_string_lossy()))
+            .spawn(move || decode(child, src, size, fps, tx, spare_rx))
+            .unwrap();
+        Decoder { frames: rx, spare: spare_tx }
+    }
+
+    /// blocks until the next frame is decoded, returns None once the clip has ended
+    pub(crate) fn next_frame(&self) -> Option<OutputVideoFrame> {
+        self.frames.recv().ok()
+    }
+
+    /// hand a used frame buffer back so a later frame is decoded into it instead of a new allocation
+    pub(crate) fn recycle(&self, data: Vec<u8>) {
+        let _ = self.spare.try_send(data);
+    }
+}
+
+/// audio of a single clip as raw stereo pcm, read in step with the clip's video frames
+///
+/// ffmpeg's pipe buffer keeps the audio ahead of the reads, dropping the decoder kills ffmpeg
+pub(crate) struct AudioDecoder {
+    child: FfmpegChild,
+    /// None once the clip's audio has ended, or if it has no audio stream
+    pcm: Option<ChildStdout>,
+}
+
+impl AudioDecoder {
+    /// decode from `start` seconds into the clip
+    pub(crate) fn spawn(src: &Path, start: f64) -> AudioDecoder {
+        let mut ffm = FfmpegCommand::new();
+        ffm.args(["-loglevel", "error"]);
+        if start > 0.0 {
+            ffm.seek(format!("{:.3}", start));
+        }
+        ffm.input(src.to_str().unwrap())
+            .no_video()
+            .args(["-ac", "2", "-ar", &SAMPLE_RATE.to_string(), "-f", "s16le"])
+            .output("pipe:1");
+        let mut child = ffm.spawn().unwrap();
+        let pcm = child.take_stdout();
+        // stderr has to be drained, clips without audio report it here
+        let stderr = child.take_stderr().unwrap();
+        let src = src.to_path_buf();
+        thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                eprintln!("LOG Audio : {}  --  {:?}", line, src);
+            }
+        });
+        AudioDecoder { child, pcm }
+    }
+
+    /// append the next `samples` samples to `out`, padded with silence once the clip's audio has ended
+    pub(crate) fn read(&mut self, samples: usize, out: &mut Vec<u8>) {
+        let start = out.len();
+        out.resize(start + samples * SAMPLE_BYTES, 0);
+        let Some(pcm) = self.pcm.as_mut() else { return };
+        let mut filled = start;
+        while filled < out.len() {
+            match pcm.read(&mut out[filled..]) {
+                Ok(0) | Err(_) => {
+                    self.pcm = None;
+                    return;
+                }
+                Ok(n) => filled += n,
+            }
+        }
+    }
+}
+
+impl Drop for AudioDecoder {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// decoding thread, runs until the clip ends or the [Decoder] is dropped
+///
+/// frames are read straight from ffmpeg's stdout into recycled buffers,
+/// stderr is parsed on a second thread for log messages
+fn decode(mut child: FfmpegChild, src: PathBuf, (width, height): (u32, u32), fps: f32,
+          frames: SyncSender<OutputVideoFrame>, spare: Receiver<Vec<u8>>) {
+    let mut stdout = child.take_stdout().unwrap();
+    let log = match child.iter() {
+        Ok(events) => {
+            let src = src.clone();
+            thread::spawn(move || log_events(events, src))
+        }
+        Err(e) => {
+            eprintln!("LOG Error : {}  --  {:?}", e, src);
+            let _ = child.kill();
+            let _ = child.wait();
+            return;
+        }
+    };
+
+    let frame_len = (width * height * 3) as usize;
+    let mut frame_num = 0u32;
+    loop {
+        let mut data = spare.try_recv().unwrap_or_default();
+        data.resize(frame_len, 0);
+        // a short read is the end of the clip
+        if stdout.read_exact(&mut data).is_err() {
+            eprintln!("Done: {:?}", src);
+            break;
+        }
+        let frame = OutputVideoFrame {
+            width,
+            height,
+            pix_fmt: "rgb24".to_string(),
+            output_index: 0,
+            data,
+            frame_num,
+            timestamp: frame_num as f32 / fps,
+        };
+        // the receiver is gone when the tile was reshaped or the output has ended
+        match frames.send(frame) {
+            Ok(_) => frame_num += 1,
+            Err(_) => {
+                let _ = child.kill();
+                break;
+            }
+        }
+    }
+    let _ = child.wait();
+    let _ = log.join();
+}
+
+fn log_events(events: FfmpegIterator, src: PathBuf) {
+    for event in events {
+        match event {
+            FfmpegEvent::Log(level, data) => {
+                match level {
+                    LogLevel::Info => {
+                        #[cfg(feature = "hyperDebug")]
+                        helper_functions::parse_debug("LogLevel::Info", file!(), line!());
+                        #[cfg(feature = "hyperDebug")]
+                        eprintln!("LOG {:?} : {}", level, data);
+                    }
+                    LogLevel::Warning | LogLevel::Error | LogLevel::Fatal => {
+                        eprintln!("LOG {:?} : {}  --  {:?}", level, data, src);
+                    }
+                    LogLevel::Unknown => {
+                        if data.len() > 2 {
+                            eprintln!("LOG {:?} : {}", level, data)
+                        }
+                    }
+                }
+            }
+            // Ignored events
+            #[cfg(feature = "hyperDebug")]
+            FfmpegEvent::ParsedConfiguration(f) => {
+                eprintln!("LOG {:?} ", f);
+            }
+            #[cfg(feature = "hyperDebug")]
+            FfmpegEvent::ParsedVersion(f) => {
+                eprintln!("LOG {:?} ", f);
+            }
+            #[cfg(feature = "hyperDebug")]
+            FfmpegEvent::Progress(f) => {
+                eprintln!("LOG {:?} ", f);
+            }
+            _ => {}
+        }
+    }
+}
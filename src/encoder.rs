@@ -0,0 +1,282 @@
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{Shutdown, TcpListener};
+use std::path::{Path, PathBuf};
+use std::process::ChildStdin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use ffmpeg_sidecar::child::FfmpegChild;
+use ffmpeg_sidecar::command::FfmpegCommand;
+
+#[cfg(feature = "hyperDebug")]
+use crate::helper_functions;
+
+/// output canvases in use, one is joined into while the other is written to ffmpeg
+pub(crate) const CANVAS_COUNT: usize = 2;
+/// ffmpeg log lines kept for error messages
+const LOG_TAIL: usize = 10;
+/// live audio is raw stereo `s16le` at this rate
+pub(crate) const SAMPLE_RATE: u32 = 48000;
+/// bytes per stereo sample
+pub(crate) const SAMPLE_BYTES: usize = 4;
+
+/// why the encoder failed, built from ffmpeg's exit status and error log
+#[derive(Debug, PartialEq)]
+pub(crate) enum EncoderError {
+    /// ffmpeg could not be started at all
+    FfmpegNotFound(String),
+    /// the `-c:v` encoder is not part of this ffmpeg build or the hardware for it is missing
+    EncoderNotAvailable(String),
+    /// the output file could not be created
+    OutputNotWritable(PathBuf, String),
+    /// anything else, with the exit code and the last lines of the log
+    Failed(Option<i32>, Vec<String>),
+}
+
+impl Display for EncoderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncoderError::FfmpegNotFound(e) => {
+                write!(f, "ffmpeg could not be started: {}\n\tcheck ffmpeg is installed and on the PATH", e)
+            }
+            EncoderError::EncoderNotAvailable(log) => {
+                write!(f, "encoder not available: {}\n\tcheck the encoder in settings.ini or the \
+                    --encode-amd / --encode-nvidea switches match your hardware", log)
+            }
+            EncoderError::OutputNotWritable(path, log) => {
+                write!(f, "output not writable: {:?}\n\t{}", path, log)
+            }
+            EncoderError::Failed(code, log) => {
+                write!(f, "encoder failed with exit code {:?}", code)?;
+                for line in log {
+                    write!(f, "\n\t{}", line)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// local tcp inputs that carry the live audio of each group into the encoder,
+/// a second pipe is not available on every platform
+pub(crate) struct AudioInputs {
+    listener: TcpListener,
+    count: usize,
+}
+
+impl AudioInputs {
+    pub(crate) fn bind(count: usize) -> std::io::Result<AudioInputs> {
+        Ok(AudioInputs { listener: TcpListener::bind("127.0.0.1:0")?, count })
+    }
+
+    /// add one raw pcm input per group, ffmpeg connects to them in input order once it has started.
+    /// the inputs are numbered from 1, after the video on `pipe:0`
+    pub(crate) fn add_inputs(&self, ffm: &mut FfmpegCommand) -> std::io::Result<()> {
+        let url = format!("tcp://{}", self.listener.local_addr()?);
+        for _ in 0..self.count {
+            ffm.args([
+                "-f", "s16le",
+                "-ar", &SAMPLE_RATE.to_string(),
+                "-ac", "2",
+                // the format is known, only wait for the first bytes
+                "-probesize", "32",
+                "-thread_queue_size", "1024",
+            ]).input(&url);
+        }
+        Ok(())
+    }
+}
+
+/// the encoding ffmpeg process, joined frames are written to it on its own thread
+///
+/// canvases are handed back once written, so joining the next frame overlaps with
+/// writing the last one and no per frame allocation is needed
+pub(crate) struct EncoderSink {
+    child: FfmpegChild,
+    output: PathBuf,
+    /// canvases ready to be joined into
+    canvases: Receiver<Vec<u8>>,
+    frames: SyncSender<Vec<u8>>,
+    writer: JoinHandle<std::io::Result<()>>,
+    log: JoinHandle<Vec<String>>,
+    /// pcm for each group's audio input, see [AudioInputs]
+    audio: Vec<Sender<Vec<u8>>>,
+    audio_writer: Option<JoinHandle<std::io::Result<()>>>,
+    /// stops waiting for audio connections once ffmpeg has exited
+    audio_stop: Arc<AtomicBool>,
+}
+
+impl EncoderSink {
+    /// start `ffm`, which must read `frame_len` byte raw frames from stdin and write to `output`,
+    /// `audio` must have been added to `ffm` with [AudioInputs::add_inputs]
+    pub(crate) fn spawn(mut ffm: FfmpegCommand, output: PathBuf, frame_len: usize,
+                        audio: Option<AudioInputs>) -> Result<EncoderSink, EncoderError> {
+        let mut child = ffm.spawn().map_err(|e| EncoderError::FfmpegNotFound(e.to_string()))?;
+        let stdin = child.take_stdin().unwrap();
+        let stderr = child.take_stderr().unwrap();
+
+        let (frames_tx, frames_rx) = sync_channel::<Vec<u8>>(CANVAS_COUNT);
+        let (canvas_tx, canvas_rx) = sync_channel(CANVAS_COUNT);
+        for _ in 0..CANVAS_COUNT {
+            canvas_tx.send(vec![0u8; frame_len]).unwrap();
+        }
+        let writer = thread::Builder::new()
+            .name("encoder pipe".to_string())
+            .spawn(move || write_frames(stdin, frames_rx, canvas_tx))
+            .unwrap();
+        // keep the end of the log for error messages, everything is still printed
+        let log = thread::spawn(move || {
+            let mut tail = vec![];
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                eprintln!("ENCODER: {}", line);
+                if tail.len() == LOG_TAIL {
+                    tail.remove(0);
+                }
+                tail.push(line);
+            }
+            tail
+        });
+
+        let audio_stop = Arc::new(AtomicBool::new(false));
+        let (audio_tx, audio_writer) = match audio {
+            None => (vec![], None),
+            Some(inputs) => {
+                // unbounded, ffmpeg reads the audio inputs one at a time while it starts up
+                let (senders, receivers): (Vec<_>, Vec<_>) = (0..inputs.count).map(|_| channel()).unzip();
+                let stop = audio_stop.clone();
+                (senders, Some(thread::spawn(move || write_audio(inputs.listener, receivers, stop))))
+            }
+        };
+        Ok(EncoderSink {
+            child, output, canvases: canvas_rx, frames: frames_tx, writer, log,
+            audio: audio_tx, audio_writer, audio_stop,
+        })
+    }
+
+    /// queue the pcm of a single frame for group `group`, false once the encoder has stopped
+    pub(crate) fn write_audio(&self, group: usize, pcm: Vec<u8>) -> bool {
+        self.audio[group].send(pcm).is_ok()
+    }
+
+    /// a canvas to join the next frame into, blocks until one has been written.
+    /// None once the encoder has stopped, [EncoderSink::finish] gives the reason
+    pub(crate) fn canvas(&self) -> Option<Vec<u8>> {
+        self.canvases.recv().ok()
+    }
+
+    /// queue a joined canvas to be written, false once the encoder has stopped
+    pub(crate) fn write(&self, canvas: Vec<u8>) -> bool {
+        self.frames.send(canvas).is_ok()
+    }
+
+    /// write every queued frame, close stdin so ffmpeg finalises the file, then wait for it to exit
+    pub(crate) fn finish(mut self) -> Result<(), EncoderError> {
+        drop(self.frames);
+        self.audio.clear();
+        let written = self.writer.join().unwrap_or_else(|_| Err(ErrorKind::Other.into()));
+        println!("waiting for the encoder to finish");
+        let status = self.child.wait().map_err(|e| EncoderError::Failed(None, vec![e.to_string()]))?;
+        self.audio_stop.store(true, Ordering::Relaxed);
+        let audio_written = match self.audio_writer.take() {
+            Some(w) => w.join().unwrap_or_else(|_| Err(ErrorKind::Other.into())),
+            None => Ok(()),
+        };
+        let written = written.and(audio_written);
+        let log = self.log.join().unwrap_or_default();
+        match (status.success(), written) {
+            (true, Ok(_)) => Ok(()),
+            (true, Err(e)) => Err(EncoderError::Failed(status.code(), vec![format!("encoder stopped reading frames: {}", e)])),
+            (false, _) => Err(classify(status.code(), log, &self.output)),
+        }
+    }
+}
+
+/// write each canvas in full, stdin is closed when the sender is dropped
+fn write_frames(mut stdin: ChildStdin, frames: Receiver<Vec<u8>>, canvases: SyncSender<Vec<u8>>) -> std::io::Result<()> {
+    for canvas in frames {
+        #[cfg(feature = "hyperDebug")]
+        helper_functions::parse_debug("main_loop: frame_write", file!(), line!());
+        stdin.write_all(&canvas)?;
+        // the joiner may have finished already
+        let _ = canvases.send(canvas);
+    }
+    stdin.flush()
+}
+
+/// accept ffmpeg's connection to each audio input in order, then write each group's pcm to it
+fn write_audio(listener: TcpListener, groups: Vec<Receiver<Vec<u8>>>, stop: Arc<AtomicBool>) -> std::io::Result<()> {
+    listener.set_nonblocking(true)?;
+    let mut writers = vec![];
+    for pcm in groups {
+        let mut stream = loop {
+            match listener.accept() {
+                Ok((stream, _)) => break stream,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    if stop.load(Ordering::Relaxed) {
+                        return Err(ErrorKind::NotConnected.into());
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        stream.set_nonblocking(false)?;
+        writers.push(thread::spawn(move || {
+            for chunk in pcm {
+                stream.write_all(&chunk)?;
+            }
+            // end of stream for this input
+            stream.shutdown(Shutdown::Write)
+        }));
+    }
+    for w in writers {
+        w.join().unwrap_or_else(|_| Err(ErrorKind::Other.into()))?;
+    }
+    Ok(())
+}
+
+/// pick the most useful error from the end of ffmpeg's log
+fn classify(code: Option<i32>, log: Vec<String>, output: &Path) -> EncoderError {
+    const ENCODER: [&str; 5] = [
+        "Unknown encoder", "Encoder not found", "Error while opening encoder",
+        "Could not open encoder", "No capable devices found",
+    ];
+    const OUTPUT: [&str; 3] = ["Permission denied", "No such file or directory", "Read-only file system"];
+
+    if let Some(line) = log.iter().find(|l| ENCODER.iter().any(|e| l.contains(e))) {
+        return EncoderError::EncoderNotAvailable(line.clone());
+    }
+    if let Some(line) = log.iter().find(|l| OUTPUT.iter().any(|e| l.contains(e))) {
+        return EncoderError::OutputNotWritable(output.to_path_buf(), line.clone());
+    }
+    EncoderError::Failed(code, log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_encoder_log() {
+        let out = Path::new("/out/video.mp4");
+        let log = |l: &str| vec!["Input #0, rawvideo, from 'pipe:0':".to_string(), l.to_string()];
+
+        assert_eq!(
+            classify(Some(1), log("Unknown encoder 'hevc_amf'"), out),
+            EncoderError::EncoderNotAvailable("Unknown encoder 'hevc_amf'".to_string())
+        );
+        assert!(matches!(
+            classify(Some(1), log("[h264_nvenc @ 0x1] No capable devices found"), out),
+            EncoderError::EncoderNotAvailable(_)
+        ));
+        assert_eq!(
+            classify(Some(1), log("/out/video.mp4: Permission denied"), out),
+            EncoderError::OutputNotWritable(out.to_path_buf(), "/out/video.mp4: Permission denied".to_string())
+        );
+        assert!(matches!(classify(Some(187), log("Conversion failed!"), out), EncoderError::Failed(Some(187), l) if l.len() == 2));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::encoder::{SAMPLE_BYTES, SAMPLE_RATE};
+use crate::switches::AudioFocus;
+
+/// gain of tiles out of focus for `loudest` and `rotate`, about -12dB
+const DUCK_GAIN: f32 = 0.25;
+/// seconds for a gain change to complete
+const RAMP_SECS: f32 = 0.5;
+/// seconds of audio the short-term loudness is averaged over
+const LOUDNESS_SECS: f32 = 1.5;
+/// `loudest` only moves to a tile this many times louder than the focused tile (+3dB)
+const SWITCH_RATIO: f32 = 2.0;
+/// seconds `loudest` stays on a tile before it can move again
+const MIN_FOCUS_SECS: f32 = 2.0;
+
+/// per group gains for the live audio, applied to each frame's pcm before it reaches the encoder
+pub(crate) struct Focus {
+    mode: AudioFocus,
+    /// group shown in the largest tile
+    hero: usize,
+    focused: usize,
+    /// frames since the focus last moved
+    focus_frames: u64,
+    gains: Vec<f32>,
+    /// short-term mean square of each group
+    loudness: Vec<f32>,
+}
+
+impl Focus {
+    pub(crate) fn new(mode: AudioFocus, groups: usize, hero: usize) -> Focus {
+        let gains = (0..groups).map(|i| match mode {
+            AudioFocus::All => 1.0,
+            _ if i == hero => 1.0,
+            AudioFocus::Hero => 0.0,
+            _ => DUCK_GAIN,
+        }).collect();
+        Focus { mode, hero, focused: hero, focus_frames: 0, gains, loudness: vec![0.0; groups] }
+    }
+
+    /// scale frame `fc` (counted from 1) of every group's pcm by its gain,
+    /// gains move towards the focused tile over `RAMP_SECS`
+    pub(crate) fn apply(&mut self, fc: u64, fps: f32, pcm: &mut [Vec<u8>]) {
+        if self.mode == AudioFocus::All {
+            return;
+        }
+        self.focus_frames += 1;
+        self.measure(pcm, fps);
+        let next = match self.mode {
+            AudioFocus::All | AudioFocus::Hero => self.hero,
+            AudioFocus::Rotate(secs) => {
+                let turn = ((fc - 1) as f64 / fps as f64 / secs) as usize;
+                (self.hero + turn) % self.gains.len()
+            }
+            AudioFocus::Loudest => self.loudest(fps),
+        };
+        if next != self.focused {
+            self.focused = next;
+            self.focus_frames = 0;
+        }
+
+        for (i, group) in pcm.iter_mut().enumerate() {
+            let target = match (i == self.focused, &self.mode) {
+                (true, _) => 1.0,
+                (false, AudioFocus::Hero) => 0.0,
+                (false, _) => DUCK_GAIN,
+            };
+            let samples = group.len() / SAMPLE_BYTES;
+            let step = samples as f32 / (RAMP_SECS * SAMPLE_RATE as f32);
+            let start = self.gains[i];
+            let end = start + (target - start).clamp(-step, step);
+            self.gains[i] = end;
+            scale(group, start, end);
+        }
+    }
+
+    /// update the short-term loudness with this frame's pcm
+    fn measure(&mut self, pcm: &[Vec<u8>], fps: f32) {
+        let weight = 1.0 / (LOUDNESS_SECS * fps).max(1.0);
+        for (l, group) in self.loudness.iter_mut().zip(pcm) {
+            let samples = group.chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0);
+            let (sum, count) = samples.fold((0.0, 0), |(sum, count), s| (sum + s * s, count + 1));
+            let mean = if count == 0 { 0.0 } else { sum / count as f32 };
+            *l += (mean - *l) * weight;
+        }
+    }
+
+    /// the loudest group, the focus only moves when another group is clearly louder
+    /// and the focused group has been heard for at least `MIN_FOCUS_SECS`
+    fn loudest(&self, fps: f32) -> usize {
+        let (loudest, level) = self.loudness.iter()
+            .enumerate()
+            .fold((self.focused, 0.0), |best, (i, &l)| if l > best.1 { (i, l) } else { best });
+        let settled = self.focus_frames as f32 >= MIN_FOCUS_SECS * fps;
+        match settled && level > self.loudness[self.focused] * SWITCH_RATIO {
+            true => loudest,
+            false => self.focused,
+        }
+    }
+}
+
+/// scale s16le samples by a gain ramping linearly from `start` to `end`
+fn scale(pcm: &mut [u8], start: f32, end: f32) {
+    if start == 1.0 && end == 1.0 {
+        return;
+    }
+    let samples = (pcm.len() / SAMPLE_BYTES).max(1) as f32;
+    for (i, frame) in pcm.chunks_exact_mut(SAMPLE_BYTES).enumerate() {
+        let gain = start + (end - start) * i as f32 / samples;
+        for s in frame.chunks_exact_mut(2) {
+            let v = i16::from_le_bytes([s[0], s[1]]) as f32 * gain;
+            s.copy_from_slice(&(v as i16).to_le_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(level: i16, samples: usize) -> Vec<u8> {
+        (0..samples * 2).flat_map(|i| if i % 4 < 2 { level } else { -level }.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn focus_ramps_and_follows() {
+        let fps = 30.0;
+        // hero keeps only the hero tile, the others are silent
+        let mut hero = Focus::new(AudioFocus::Hero, 3, 1);
+        let mut last = vec![];
+        for fc in 1..=30 {
+            last = vec![tone(1000, 1600); 3];
+            hero.apply(fc, fps, &mut last);
+        }
+        assert!(last[0].iter().all(|&b| b == 0) && last[2].iter().all(|&b| b == 0));
+        assert_eq!(last[1], tone(1000, 1600));
+
+        // rotate moves on after each period, starting from the hero
+        let mut rotate = Focus::new(AudioFocus::Rotate(1.0), 3, 1);
+        for fc in 1..=31 {
+            rotate.apply(fc, fps, &mut vec![tone(1000, 1600); 3]);
+        }
+        assert_eq!(rotate.focused, 2);
+
+        // loudest moves to a clearly louder group once the focus has settled
+        let mut loudest = Focus::new(AudioFocus::Loudest, 3, 0);
+        for fc in 1..=90 {
+            loudest.apply(fc, fps, &mut [tone(500, 1600), tone(500, 1600), tone(8000, 1600)]);
+        }
+        assert_eq!(loudest.focused, 2);
+        assert!((loudest.gains[2] - 1.0).abs() < 1e-6 && (loudest.gains[0] - DUCK_GAIN).abs() < 1e-6);
+    }
+}
@@ -1,59 +1,92 @@
+use std::collections::{HashMap, HashSet};
+
 use crate::frame_shape::FrameShape;
 
+/// what a filter in a slot's chain changes, overrides replace or add to these
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    Pan,
+    Gain,
+    /// a chain from settings.ini
+    Custom,
+}
+
 struct FfmAudioText {
-    inputs: Vec<char>,
-    text: Vec<String>,
+    inputs: Vec<String>,
+    /// filters applied to each input, in order
+    chains: Vec<Vec<(Stage, String)>>,
 }
 
 impl FfmAudioText {
     pub fn new() -> Self {
         FfmAudioText {
             inputs: Vec::new(),
-            text: Vec::new(),
+            chains: Vec::new(),
         }
     }
     fn add_input(mut self) -> Self {
         let number = self.inputs.len();
-        let character = char::from_u32((number + 97) as u32).unwrap();
+        // a-z for the first inputs, larger grids fall back to numbered labels
+        let character = match number {
+            0..=25 => char::from_u32((number + 97) as u32).unwrap().to_string(),
+            _ => format!("t{}", number),
+        };
         self.inputs.push(character);
-        self.text.push(format!("[{}:a]anull[{}];", number + 1, character));
+        self.chains.push(Vec::new());
         self
     }
-    fn stereo_bal(mut self, bal: f32) -> Self {
-        let character = self.inputs.last().unwrap();
-        self.text.last_mut().unwrap().push_str(
-            format!("[{}]stereotools=balance_in=-{}[{}];", character, bal, character).as_str()
-        );
+    fn push(mut self, stage: Stage, filter: String) -> Self {
+        self.chains.last_mut().unwrap().push((stage, filter));
         self
     }
-    fn surround(mut self, angle: i32) -> Self {
-        let character = self.inputs.last().unwrap();
+    fn stereo_bal(self, bal: f32) -> Self {
+        self.push(Stage::Pan, format!("stereotools=balance_in={}", -bal))
+    }
+    fn surround(self, angle: i32) -> Self {
         let angle = if angle > 360 { angle } else if angle < 0 { 360 + angle } else { angle };
         assert!((angle < 360) && (angle > -1), "Error in angles");
 
-        self.text.last_mut().unwrap().push_str(
-            format!("[{}]surround=chl_out=stereo:chl_in=stereo:angle={}[{}];", character, angle, character).as_str()
-        );
-        self
+        self.push(Stage::Pan, format!("surround=chl_out=stereo:chl_in=stereo:angle={}", angle))
     }
-    fn volume(mut self, db: i32) -> Self {
-        let character = self.inputs.last().unwrap();
-        self.text.last_mut().unwrap().push_str(
-            format!("[{}]volume={}dB[{}];", character, db, character).as_str()
-        );
+    fn volume(self, db: i32) -> Self {
+        self.gain(db as f32)
+    }
+    fn gain(self, db: f32) -> Self {
+        self.push(Stage::Gain, format!("volume={}dB", db))
+    }
+    /// apply the `--mute-group`, `--group-gain`, `--group-pan` and `[Audio]` overrides, slots start at 0
+    fn with_overrides(mut self, overrides: &AudioOverrides) -> Self {
+        for (slot, chain) in self.chains.iter_mut().enumerate() {
+            if let Some(custom) = overrides.chain.get(&slot) {
+                *chain = vec![(Stage::Custom, custom.clone())];
+            }
+            if let Some(pan) = overrides.pan.get(&slot) {
+                chain.retain(|(stage, _)| *stage != Stage::Pan);
+                chain.insert(0, (Stage::Pan, format!("stereotools=balance_in={}", pan)));
+            }
+            if let Some(db) = overrides.gain.get(&slot) {
+                chain.push((Stage::Gain, format!("volume={}dB", db)));
+            }
+            if overrides.mute.contains(&slot) {
+                chain.push((Stage::Gain, "volume=0".to_string()));
+            }
+        }
         self
     }
     fn mix_out(self) -> String {
         // [a][b][c][d]amix=inputs=4[d];[d]loudnorm[d]\
         let mut txt = String::new();
-        
-        for i in self.text.iter(){
-            txt.push_str(i.as_str())
+
+        for (number, (character, chain)) in self.inputs.iter().zip(self.chains.iter()).enumerate() {
+            txt.push_str(&format!("[{}:a]anull[{}];", number + 1, character));
+            for (_, filter) in chain {
+                txt.push_str(&format!("[{}]{}[{}];", character, filter, character));
+            }
         }
-        
+
         for i in self.inputs.iter(){
             txt.push('[');
-            txt.push(*i);
+            txt.push_str(i);
             txt.push(']');
         };
         txt.push_str(
@@ -82,30 +115,100 @@ const SUR_L:i32  = -90;
 const SUR_C:i32 = 0;
 const SUR_R:i32 = 90;
 
+/// stereo balance for a tile centred at `centre`, from 0.0 (left edge) to 1.0 (right edge)
+/// returns -0.9 to 0.9, the same range as `BALANCE_LR`
+pub(crate) fn position_pan(centre: f32) -> f32 {
+    (centre * 2.0 - 1.0) * BALANCE_LR[0]
+}
+
+
+/// per group changes to the generated audio mix, groups are stored from 0 but given from 1
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct AudioOverrides {
+    mute: HashSet<usize>,
+    /// extra gain in dB
+    gain: HashMap<usize, f32>,
+    /// -1.0 (left) to 1.0 (right), replaces the shape's pan
+    pan: HashMap<usize, f32>,
+    /// ffmpeg filter chain replacing everything generated for the group
+    chain: HashMap<usize, String>,
+}
+
+impl AudioOverrides {
+    /// `3`, the group is kept in the mix but silent
+    pub(crate) fn mute(&mut self, group: &str) -> Result<(), String> {
+        self.mute.insert(parse_group(group)?);
+        Ok(())
+    }
+
+    /// `2=-6dB`, the `dB` is optional
+    pub(crate) fn gain(&mut self, arg: &str) -> Result<(), String> {
+        let (group, value) = split_group(arg)?;
+        let value = value.trim();
+        let value = value.strip_suffix("dB").or_else(|| value.strip_suffix("db")).unwrap_or(value);
+        let db = value.trim().parse::<f32>().map_err(|_| format!("gain not a number: {}", arg))?;
+        self.gain.insert(group, db);
+        Ok(())
+    }
+
+    /// `1=-0.8`
+    pub(crate) fn pan(&mut self, arg: &str) -> Result<(), String> {
+        let (group, value) = split_group(arg)?;
+        let pan = value.trim().parse::<f32>().map_err(|_| format!("pan not a number: {}", arg))?;
+        if !(-1.0..=1.0).contains(&pan) {
+            return Err(format!("pan must be between -1.0 and 1.0: {}", arg));
+        }
+        self.pan.insert(group, pan);
+        Ok(())
+    }
+
+    /// replace the generated filters of group `group` with `chain`
+    pub(crate) fn chain(&mut self, group: &str, chain: &str) -> Result<(), String> {
+        if chain.trim().is_empty() {
+            return Err(format!("empty audio chain for group {}", group));
+        }
+        self.chain.insert(parse_group(group)?, chain.trim().to_string());
+        Ok(())
+    }
+}
+
+fn parse_group(group: &str) -> Result<usize, String> {
+    match group.trim().parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n - 1),
+        _ => Err(format!("groups are numbered from 1: {}", group)),
+    }
+}
+
+fn split_group(arg: &str) -> Result<(usize, &str), String> {
+    let (group, value) = arg.split_once('=').ok_or(format!("expected <group>=<value>: {}", arg))?;
+    Ok((parse_group(group)?, value))
+}
 
 impl FrameShape {
+    /// audio filter mixing every tile into `[d]`, tile n is read from input n + 1
+    pub(crate) fn audio_args_with_vid(&self, overrides: &AudioOverrides) -> String {
+        self.audio_mix().with_overrides(overrides).mix_out()
+    }
+
     //noinspection SpellCheckingInspection
-    pub(crate) fn audio_args_with_vid(&self) -> String {
+    fn audio_mix(&self) -> FfmAudioText {
         match self {
             FrameShape::Mono  => {
                 let out = FfmAudioText::new()
-                    .add_input()
-                    .mix_out();
+                    .add_input();
                 out
             }
             FrameShape::Dual => {
                 let out = FfmAudioText::new()
                     .add_input().stereo_lc(-0)
-                    .add_input().stereo_rc(-0)
-                    .mix_out();
+                    .add_input().stereo_rc(-0);
                 out
             }
             FrameShape::Triple => {
                 let out = FfmAudioText::new()
                     .add_input().stereo_l(-3)
                     .add_input().stereo_c(0)
-                    .add_input().stereo_r(-3)
-                    .mix_out();
+                    .add_input().stereo_r(-3);
                 out
             }
             FrameShape::Quad => {
@@ -114,8 +217,7 @@ impl FrameShape {
                     .add_input().stereo_lc(0)
                     .add_input().stereo_rc(0)
                     .add_input().stereo_l(-3)
-                    .add_input().stereo_r(-3)
-                    .mix_out();
+                    .add_input().stereo_r(-3);
                 out 
             }
             FrameShape::VertEmph | FrameShape::VertEmph2 => {
@@ -124,8 +226,7 @@ impl FrameShape {
                     .add_input().stereo_l(-3)
                     .add_input().stereo_r(-3)
                     .add_input().stereo_l(-5)
-                    .add_input().stereo_r(-5)
-                    .mix_out();
+                    .add_input().stereo_r(-5);
                 out 
             }
             FrameShape::HorizEmph | FrameShape::HorizEmph2 => {
@@ -133,24 +234,21 @@ impl FrameShape {
                     .add_input().stereo_l(-3)
                     .add_input().stereo_c(0)
                     .add_input().stereo_c(-5) 
-                    .add_input().stereo_r(-3)
-                    .mix_out();
+                    .add_input().stereo_r(-3);
                 out 
             }
             FrameShape::SideVert => {
                 let out = FfmAudioText::new()
                     .add_input().stereo_l(0)
                     .add_input().stereo_rc(0)
-                    .add_input().stereo_rc(-3)
-                    .mix_out();
+                    .add_input().stereo_rc(-3);
                 out  
             }
             FrameShape::SideVert2 => {
                 let out = FfmAudioText::new()
                     .add_input().stereo_r(0)
                     .add_input().stereo_lc(0)
-                    .add_input().stereo_lc(-3)
-                    .mix_out();
+                    .add_input().stereo_lc(-3);
                 out  
             }
             FrameShape::CentreEmphVert | FrameShape::CentreEmphVert2 => {
@@ -159,8 +257,7 @@ impl FrameShape {
                     .add_input().stereo_l(-2)
                     .add_input().stereo_r(-2)
                     .add_input().stereo_lc(-5)
-                    .add_input().stereo_rc(-5)
-                    .mix_out();
+                    .add_input().stereo_rc(-5);
                 out  
             }
             FrameShape::MoreHoriz | FrameShape::MoreHoriz2 => {
@@ -171,8 +268,7 @@ impl FrameShape {
                     .add_input().stereo_lc(-5)
                     .add_input().stereo_rc(-5)
                     .add_input().stereo_l(-2)
-                    .add_input().stereo_r(-2)
-                    .mix_out();
+                    .add_input().stereo_r(-2);
                 out  
             }
             FrameShape::ExtendedLandscape => {
@@ -183,8 +279,7 @@ impl FrameShape {
                     .add_input().stereo_l(-2)
                     .add_input().stereo_r(-2)
                     .add_input().stereo_l(-3)
-                    .add_input().stereo_r(-3)
-                    .mix_out();
+                    .add_input().stereo_r(-3);
                 out
             }
             FrameShape::ExtendedLandscape2 => {
@@ -196,8 +291,7 @@ impl FrameShape {
                     .add_input().stereo_l(-5)
                     .add_input().stereo_r(-5)
                     .add_input().stereo_l(-6)
-                    .add_input().stereo_r(-6)
-                    .mix_out();
+                    .add_input().stereo_r(-6);
                 out
             }
             FrameShape::OffsetVH4x4 => {
@@ -209,10 +303,60 @@ impl FrameShape {
                     .add_input().stereo_l(-2)
                     .add_input().stereo_r(-2)
                     .add_input().stereo_lc(-3)
-                    .add_input().stereo_rc(-3)
-                    .mix_out();
+                    .add_input().stereo_rc(-3);
+                out
+            }
+            FrameShape::Grid { rows, cols } => {
+                // tiles are panned by column, each row repeats the same positions
+                let mut out = FfmAudioText::new();
+                for _ in 0..*rows {
+                    for c in 0..*cols {
+                        let pan = position_pan((c as f32 + 0.5) / *cols as f32);
+                        out = out.add_input().stereo_bal(-pan);
+                    }
+                }
+                out
+            }
+            FrameShape::Custom(layout) => {
+                // layout pan runs -1.0 (left) to 1.0 (right), stereo_bal takes the inverse
+                let mut out = FfmAudioText::new();
+                for (pan, gain) in layout.audio_mix() {
+                    out = out.add_input().stereo_bal(-pan).gain(gain);
+                }
                 out
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audio_overrides() {
+        let mut overrides = AudioOverrides::default();
+        overrides.mute("3").unwrap();
+        overrides.gain("2=-6dB").unwrap();
+        overrides.pan("1=-0.8").unwrap();
+        assert!(overrides.gain("0=3").is_err());
+        assert!(overrides.pan("1=2").is_err());
+        assert!(overrides.gain("2").is_err());
+
+        // nothing overridden keeps the generated mix
+        assert_eq!(
+            FrameShape::Dual.audio_args_with_vid(&AudioOverrides::default()),
+            "[1:a]anull[a];[a]stereotools=balance_in=-0.45[a];[a]surround=chl_out=stereo:chl_in=stereo:angle=0[a];[a]volume=0dB[a];\
+             [2:a]anull[b];[b]stereotools=balance_in=0.45[b];[b]surround=chl_out=stereo:chl_in=stereo:angle=0[b];[b]volume=0dB[b];\
+             [a][b]amix=inputs=2[d];[d]loudnorm[d]"
+        );
+        let mix = FrameShape::Triple.audio_args_with_vid(&overrides);
+        assert!(mix.starts_with("[1:a]anull[a];[a]stereotools=balance_in=-0.8[a];[a]volume=-3dB[a];[2:a]"));
+        assert!(mix.contains("[b]volume=0dB[b];[b]volume=-6dB[b];"));
+        assert!(mix.contains("[c]volume=-3dB[c];[c]volume=0[c];"));
+
+        overrides.chain("2", "highpass=f=200").unwrap();
+        let mix = FrameShape::Triple.audio_args_with_vid(&overrides);
+        assert!(mix.contains("[2:a]anull[b];[b]highpass=f=200[b];[b]volume=-6dB[b];[3:a]"));
+    }
+}
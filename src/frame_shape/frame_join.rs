@@ -1,522 +1,269 @@
-use crate::frame_shape::FrameShape;
+use crate::frame_shape::{FrameShape, TileRect, FRAME_SHAPE_MAPPING};
 use crate::video::{Joiner, VideoEditData};
 use ffmpeg_sidecar::event::OutputVideoFrame;
-use std::ops::Rem;
-use std::slice::ChunksExact;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// most threads used to copy tiles into the output frame
+const MAX_BLIT_THREADS: usize = 8;
+/// bands smaller than this are not worth a thread
+const MIN_BAND_ROWS: usize = 64;
+
+fn blit_threads() -> usize {
+    static THREADS: OnceLock<usize> = OnceLock::new();
+    *THREADS.get_or_init(|| {
+        std::thread::available_parallelism().map_or(1, |n| n.get()).min(MAX_BLIT_THREADS)
+    })
+}
+
+/// threads that fill the bands of each output frame, started once and reused for every frame
+/// rather than started again per frame. the joining thread fills a band itself, so the pool
+/// has one thread fewer than [blit_threads] and none on a single core
+pub(crate) struct BlitPool {
+    pool: Option<rayon::ThreadPool>,
+}
+
+impl BlitPool {
+    pub(crate) fn new() -> BlitPool {
+        let pool = (blit_threads() > 1).then(|| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(blit_threads() - 1)
+                .thread_name(|i| format!("blit {}", i + 1))
+                .build()
+                .unwrap()
+        });
+        BlitPool { pool }
+    }
+}
 
 impl Joiner for FrameShape {
     /// join methods for a single frame.
     ///
-    /// new frame shapes must be implemented here
+    /// each frame is copied into the output canvas at the tile position given by
+    /// `out_sh.rects`, new frame shapes only need to be added to [FrameShape::tile_rects]
+    ///
+    /// the canvas is split into horizontal bands that are filled on the threads of `pool`,
+    /// areas not covered by a tile are left as they are
     ///
-    /// # Arguments 
+    /// # Arguments
     ///
     /// * `frames`: list of frames from each video input
     /// * `out_sh`: shape data
+    /// * `out`: output canvas of `output_width * output_height * 3` bytes, reused between frames
+    /// * `pool`: blit threads, reused between frames
     ///
-    /// returns: Vec<u8, Global> 
-    ///
-    fn frame_joiner(&self, mut frames: Vec<OutputVideoFrame>, out_sh: &VideoEditData) -> Vec<u8> {
-        let mut out =
-            Vec::with_capacity((&out_sh.output_height * &out_sh.output_width * 3) as usize);
+    fn frame_joiner(&self, frames: &[OutputVideoFrame], out_sh: &VideoEditData, out: &mut [u8], pool: &BlitPool) {
+        let stride = (out_sh.output_width * 3) as usize;
+        let height = out_sh.output_height as usize;
+        let Some(pool) = pool.pool.as_ref().filter(|_| height >= MIN_BAND_ROWS * 2) else {
+            blit_band(out, 0, stride, frames, &out_sh.rects);
+            return;
+        };
+        let threads = blit_threads().min(height / MIN_BAND_ROWS);
+        let band_rows = height.div_ceil(threads);
 
-        let mut chunks: Vec<ChunksExact<u8>> = frames
-            .iter_mut()
-            .map(
-                |x|
-                    x.data
-                        .chunks_exact(x.width as usize * 3)
-                        .into_iter()
-            )
-            .collect();
-        match self {
-            FrameShape::Mono => {
-                'outter: loop {
-                    for chunk in chunks.iter_mut() {
-                        match chunk.next() {
-                            None => break 'outter,
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                }
-            }
-            FrameShape::Dual | FrameShape::Triple => {
-                'outter: loop {
-                    for chunk in chunks.iter_mut() {
-                        match chunk.next() {
-                            None => break 'outter,
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                }
-            }
-            FrameShape::Quad => {
-                assert_eq!(chunks.len().rem(2), 0);
-                'outter: loop {
-                    for chunk in chunks[0..2].iter_mut() {
-                        match chunk.next() {
-                            None => break 'outter,
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                }
-                'outter: loop {
-                    for chunk in chunks[2..4].iter_mut() {
-                        match chunk.next() {
-                            None => break 'outter,
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                }
-            }
-            FrameShape::VertEmph | FrameShape::VertEmph2 => {
-                'outter: loop {
-                    match chunks[1].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                    match chunks[0].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                    match chunks[2].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                }
-                'outter: loop {
-                    match chunks[3].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                    match chunks[0].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                    match chunks[4].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                }
-            }
-            FrameShape::HorizEmph | FrameShape::HorizEmph2 => {
-                let mut switch: bool = true;
-                'outter: loop {
-                    match chunks[0].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                    if switch {
-                        match chunks[1].next() {
-                            None => { switch = false }
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                    // Important note: Do not change this to an else statement
-                    // if switch is None !switch must happen
-                    if !switch {
-                        match chunks[2].next() {
-                            None => break 'outter,
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                    match chunks[3].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                }
-            }
-            FrameShape::SideVert => {
-                let mut switch: bool = true;
-                'outter: loop {
-                    match chunks[0].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                    if switch {
-                        match chunks[1].next() {
-                            None => { switch = false }
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                    // Important note: Do not change this to an else statement
-                    // if switch is None !switch must happen
-                    if !switch {
-                        match chunks[2].next() {
-                            None => break 'outter,
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                }
-            }
-            FrameShape::SideVert2 => {
-                let mut switch: bool = true;
-                'outter: loop {
-                    if switch {
-                        match chunks[1].next() {
-                            None => { switch = false }
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                    // Important note: Do not change this to an else statement
-                    // if switch is None !switch must happen
-                    if !switch {
-                        match chunks[2].next() {
-                            None => break 'outter,
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                    match chunks[0].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                }
-            }
-            FrameShape::CentreEmphVert => {
-                let mut switch: bool = true;
-                'outter: loop {
-                    // this is a full height item, always iter over it
-                    match chunks[1].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                    // the top part and bottom part are diferent, once the top has been consumed
-                    // we need to use the bottom part
-                    if switch {
-                        match chunks[0].next() {
-                            None => { switch = false }
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                    // Important note: Do not change this to an else statement
-                    // if switch is None !switch must happen
-                    if !switch {
-                        match chunks[3].next() {
-                            None => break 'outter,
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                        match chunks[4].next() {
-                            None => break 'outter,
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                    // this is a full height item, always iter over it
-                    match chunks[2].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                }
+        let mut bands = out.chunks_mut(band_rows * stride).enumerate();
+        pool.in_place_scope(|s| {
+            // the first band is filled on this thread once the others are queued
+            let first = bands.next();
+            for (i, band) in bands {
+                s.spawn(move |_| blit_band(band, i * band_rows, stride, frames, &out_sh.rects));
             }
-            FrameShape::CentreEmphVert2 => {
-                let mut switch: bool = true;
-                'outter: loop {
-                    // this is a full height item, always iter over it
-                    match chunks[1].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                    // the top part and bottom part are diferent, once the top has been consumed
-                    // we need to use the bottom part
-                    if switch {
-                        match chunks[3].next() {
-                            None => { switch = false }
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                        match chunks[4].next() {
-                            None => { switch = false }
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                    // Important note: Do not change this to an else statement
-                    // if switch is None !switch must happen
-                    if !switch {
-                        match chunks[0].next() {
-                            None => break 'outter,
-                            Some(ch) => out.extend_from_slice(ch),
-                        }
-                    }
-                    // this is a full height item, always iter over it
-                    match chunks[2].next() {
-                        None => break 'outter,
-                        Some(ch) => out.extend_from_slice(ch),
-                    }
-                }
+            if let Some((_, band)) = first {
+                blit_band(band, 0, stride, frames, &out_sh.rects);
             }
-            FrameShape::MoreHoriz => {
-                let mut switch1: bool = true;
-                let mut switch2: bool = true;
-                let mut switch3: bool = true;
-                loop {
-                    if switch1 {
-                        match chunks[5].next() {
-                            None => switch1 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch1 {
-                        match chunks[1].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-
-                    if switch2 {
-                        match chunks[0].next() {
-                            None => switch2 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch2 {
-                        match chunks[3].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                        match chunks[4].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
+        });
+    }
+}
 
-                    if switch3 {
-                        match chunks[6].next() {
-                            None => switch3 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch3 {
-                        match chunks[2].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                }
-            }
-            FrameShape::MoreHoriz2 => {
-                let mut switch1: bool = true;
-                let mut switch2: bool = true;
-                let mut switch3: bool = true;
-                loop {
-                    if switch1 {
-                        match chunks[1].next() {
-                            None => switch1 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch1 {
-                        match chunks[5].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
+/// copy the rows of every tile that fall within `band`, which starts at row `band_y` of the canvas
+fn blit_band(band: &mut [u8], band_y: usize, stride: usize, frames: &[OutputVideoFrame], rects: &[TileRect]) {
+    let band_end = band_y + band.len() / stride;
+    for (frame, rect) in frames.iter().zip(rects.iter()) {
+        let row_len = (rect.w.min(frame.width) * 3) as usize;
+        let x_offset = (rect.x * 3) as usize;
+        let tile_end = rect.y as usize + rect.h.min(frame.height) as usize;
+        for y in (rect.y as usize).max(band_y)..tile_end.min(band_end) {
+            let src = (y - rect.y as usize) * frame.width as usize * 3;
+            let start = (y - band_y) * stride + x_offset;
+            band[start..start + row_len].copy_from_slice(&frame.data[src..src + row_len]);
+        }
+    }
+}
 
-                    if switch2 {
-                        match chunks[3].next() {
-                            None => switch2 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                        match chunks[4].next() {
-                            None => switch2 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch2 {
-                        match chunks[0].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
+/// join `frames` synthetic frames for every built in shape and print the frames per second,
+/// used to check the joiner is not the bottleneck, see `--bench-joiner`
+pub(crate) fn bench_joiner(width: u32, height: u32, frames: u32) {
+    let mut shapes: Vec<FrameShape> = FRAME_SHAPE_MAPPING.iter().map(|(fs, _, _)| fs.clone()).collect();
+    shapes.push(FrameShape::Grid { rows: 3, cols: 4 });
 
-                    if switch3 {
-                        match chunks[2].next() {
-                            None => switch3 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch3 {
-                        match chunks[6].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                }
+    println!("Joiner benchmark: {}x{}, {} frames per shape, {} threads", width, height, frames, blit_threads());
+    let pool = BlitPool::new();
+    for shape in shapes {
+        let mut sizer = VideoEditData::init_wxh(width, height, shape.count());
+        sizer.set_shape(shape.clone());
+        let tiles: Vec<OutputVideoFrame> = sizer.rects.iter().enumerate().map(|(i, r)| {
+            OutputVideoFrame {
+                width: r.w,
+                height: r.h,
+                pix_fmt: "rgb24".to_string(),
+                output_index: 0,
+                data: vec![i as u8; (r.w * r.h * 3) as usize],
+                frame_num: 0,
+                timestamp: 0.0,
             }
-            FrameShape::ExtendedLandscape => {
-                let mut switch1: u8 = 0u8;
-                let mut switch2: bool = true;
-                let mut switch3: u8 = 0u8;
+        }).collect();
+        let mut canvas = vec![0u8; (width * height * 3) as usize];
 
-                loop {
-                    if switch1 == 0 {
-                        match chunks[3].next() {
-                            None => switch1 += 1,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if switch1 == 1 {
-                        match chunks[5].next() {
-                            None => switch1 += 1,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if switch1 == 2 {
-                        match chunks[7].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
+        let start = Instant::now();
+        for _ in 0..frames {
+            shape.frame_joiner(&tiles, &sizer, &mut canvas, &pool);
+        }
+        let fps = frames as f64 / start.elapsed().as_secs_f64();
+        println!("{:24} {:>10.1} fps", format!("{:?}", shape), fps);
+    }
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_shape::{legacy_join, Layout};
 
-                    if switch2 {
-                        match chunks[0].next() {
-                            None => switch2 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch2 {
-                        match chunks[1].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                        match chunks[2].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
+    /// every built in shape, sizes picked so that most of the remainder branches are hit
+    fn all_shapes() -> Vec<FrameShape> {
+        vec![
+            FrameShape::Mono, FrameShape::Dual, FrameShape::Triple, FrameShape::Quad,
+            FrameShape::VertEmph, FrameShape::HorizEmph, FrameShape::VertEmph2, FrameShape::HorizEmph2,
+            FrameShape::SideVert, FrameShape::SideVert2, FrameShape::CentreEmphVert,
+            FrameShape::CentreEmphVert2, FrameShape::MoreHoriz, FrameShape::MoreHoriz2,
+            FrameShape::ExtendedLandscape, FrameShape::ExtendedLandscape2, FrameShape::OffsetVH4x4,
+            FrameShape::Grid { rows: 3, cols: 4 }, FrameShape::Grid { rows: 5, cols: 7 },
+            FrameShape::with_count(7).unwrap(), FrameShape::with_count(11).unwrap(),
+        ]
+    }
 
-                    if switch3 == 0 {
-                        match chunks[4].next() {
-                            None => switch3 += 1,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if switch3 == 1 {
-                        match chunks[6].next() {
-                            None => switch3 += 1,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if switch3 == 2 {
-                        match chunks[8].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
+    /// the row joiner sized two of ExtendedLandscape's side tiles for the other column,
+    /// where the two columns differ it wrote misaligned rows
+    fn uneven_sides(shape: &FrameShape, w: u32) -> bool {
+        matches!(shape, FrameShape::ExtendedLandscape) && (w - w / 5 * 3) % 2 == 1
+    }
+
+    #[test]
+    fn tile_rects_cover_frame_once() {
+        for (w, h) in [(2560, 1440), (1920, 1080), (403, 301), (1001, 719)] {
+            for shape in all_shapes() {
+                let rects = shape.tile_rects(w, h);
+                assert_eq!(rects.len(), shape.count() as usize, "{:?}", shape);
+                let mut hits = vec![0u8; (w * h) as usize];
+                for r in rects.iter() {
+                    assert!(r.x + r.w <= w && r.y + r.h <= h, "{:?} {:?} outside {}x{}", shape, r, w, h);
+                    for y in r.y..r.y + r.h {
+                        for x in r.x..r.x + r.w {
+                            hits[(y * w + x) as usize] += 1;
                         }
                     }
                 }
+                assert!(hits.iter().all(|&c| c == 1), "{:?} does not tile {}x{}", shape, w, h);
             }
-            FrameShape::ExtendedLandscape2 => {
-                let mut switch_vert_top1: bool = true;
-                let mut switch_vert_top2: bool = true;
-                let mut switch_vert_base: bool = true;
+            // a 2x2 grid splits the remainder the same way as Quad
+            assert_eq!(FrameShape::Grid { rows: 2, cols: 2 }.tile_rects(w, h), FrameShape::Quad.tile_rects(w, h));
+        }
+    }
 
-                loop {
-                    if switch_vert_top1 && switch_vert_base {
-                        match chunks[4].next() {
-                            None => switch_vert_top1 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch_vert_top1 && switch_vert_base {
-                        match chunks[6].next() {
-                            None => switch_vert_base = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
+    #[test]
+    fn compositor_matches_row_joiner() {
+        for (w, h) in [(2560, 1440), (1920, 1080), (640, 360), (1005, 601), (403, 301), (1001, 719), (97, 53)] {
+            for shape in all_shapes().into_iter().take(17).filter(|s| !uneven_sides(s, w)) {
+                let mut sizer = VideoEditData::init_wxh(w, h, shape.count());
+                sizer.set_shape(shape.clone());
+                let sizes: Vec<(u32, u32)> = sizer.rects.iter().map(|r| (r.w, r.h)).collect();
+                assert_eq!(sizes, legacy_join::tile_sizes(&shape, w, h), "{:?} {}x{}", shape, w, h);
 
-                    if switch_vert_base {
-                        match chunks[0].next() {
-                            None => switch_vert_base = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if switch_vert_top2 && switch_vert_base {
-                        match chunks[5].next() {
-                            None => switch_vert_top2 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch_vert_top2 && switch_vert_base {
-                        match chunks[7].next() {
-                            None => switch_vert_base = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
+                // every byte of every tile differs from its neighbours so a misplaced row shows up
+                let frames: Vec<OutputVideoFrame> = sizes.iter().enumerate().map(|(i, &(tw, th))| {
+                    OutputVideoFrame {
+                        width: tw,
+                        height: th,
+                        pix_fmt: "rgb24".to_string(),
+                        output_index: 0,
+                        data: (0..tw * th * 3).map(|b| (b as usize * 7 + i * 31) as u8).collect(),
+                        frame_num: 0,
+                        timestamp: 0.0,
+                    }
+                }).collect();
+                let mut out = vec![0u8; (w * h * 3) as usize];
+                shape.frame_joiner(&frames, &sizer, &mut out, &BlitPool::new());
+                assert!(out == legacy_join::row_joiner(&shape, &frames), "{:?} {}x{}", shape, w, h);
+            }
+        }
+    }
 
-                    if !switch_vert_base {
-                        match chunks[1].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                        match chunks[2].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                        match chunks[3].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                }
+    #[test]
+    fn frame_joiner_places_tiles() {
+        let mut sizer = VideoEditData::init_wxh(6, 4, 3);
+        sizer.set_shape(FrameShape::SideVert);
+        let frames: Vec<OutputVideoFrame> = sizer.rects.iter().enumerate().map(|(i, r)| {
+            OutputVideoFrame {
+                width: r.w,
+                height: r.h,
+                pix_fmt: "rgb24".to_string(),
+                output_index: 0,
+                data: vec![i as u8 + 1; (r.w * r.h * 3) as usize],
+                frame_num: 0,
+                timestamp: 0.0,
             }
-            FrameShape::OffsetVH4x4 => {
-                let mut switch_1: bool = true;
-                let mut switch_2: bool = true;
-                let mut switch_3: bool = true;
-                let mut switch_4: bool = true;
+        }).collect();
+        let mut out = vec![0u8; 6 * 4 * 3];
+        FrameShape::SideVert.frame_joiner(&frames, &sizer, &mut out, &BlitPool::new());
+        let pixels: Vec<u8> = out.chunks_exact(3).map(|p| p[0]).collect();
+        assert_eq!(pixels, vec![
+            1, 1, 2, 2, 2, 2,
+            1, 1, 2, 2, 2, 2,
+            1, 1, 3, 3, 3, 3,
+            1, 1, 3, 3, 3, 3,
+        ]);
 
-                loop {
-                    if switch_1{
-                        match chunks[4].next() {
-                            None => switch_1 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch_1{
-                        match chunks[2].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if switch_2{
-                        match chunks[0].next() {
-                            None => switch_2 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch_2{
-                        match chunks[6].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if switch_3{
-                        match chunks[1].next() {
-                            None => switch_3 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch_3{
-                        match chunks[7].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if switch_4{
-                        match chunks[5].next() {
-                            None => switch_4 = false,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                    if !switch_4{
-                        match chunks[3].next() {
-                            None => break,
-                            Some(ch) => out.extend_from_slice(ch)
-                        }
-                    }
-                }
+        // large frames are split into bands on several threads, every tile must still land in place
+        let (w, h) = (403, 301);
+        let mut large = VideoEditData::init_wxh(w, h, 9);
+        large.set_shape(FrameShape::ExtendedLandscape);
+        let tiles: Vec<OutputVideoFrame> = large.rects.iter().enumerate().map(|(i, r)| {
+            OutputVideoFrame {
+                width: r.w,
+                height: r.h,
+                pix_fmt: "rgb24".to_string(),
+                output_index: 0,
+                data: vec![i as u8 + 1; (r.w * r.h * 3) as usize],
+                frame_num: 0,
+                timestamp: 0.0,
+            }
+        }).collect();
+        let mut out = vec![0u8; (w * h * 3) as usize];
+        FrameShape::ExtendedLandscape.frame_joiner(&tiles, &large, &mut out, &BlitPool::new());
+        for (i, r) in large.rects.iter().enumerate() {
+            for y in r.y..r.y + r.h {
+                let row = ((y * w + r.x) * 3) as usize;
+                assert!(out[row..row + (r.w * 3) as usize].iter().all(|&p| p == i as u8 + 1), "tile {} row {}", i, y);
             }
         }
 
-
-        out
+        // areas outside of a layout file's tiles are left black
+        let layout = FrameShape::Custom(
+            Layout::from_text(r#"{"tiles": [{"x": 0.5, "y": 0, "w": 0.5, "h": 0.5}]}"#, true).unwrap()
+        );
+        sizer.set_shape(layout.clone());
+        let frame = OutputVideoFrame {
+            width: 3,
+            height: 2,
+            pix_fmt: "rgb24".to_string(),
+            output_index: 0,
+            data: vec![9; 18],
+            frame_num: 0,
+            timestamp: 0.0,
+        };
+        let mut out = vec![0u8; 6 * 4 * 3];
+        layout.frame_joiner(&[frame], &sizer, &mut out, &BlitPool::new());
+        assert_eq!(out.iter().filter(|&&p| p == 9).count(), 18);
+        assert_eq!(&out[9..18], &[9; 9]);
     }
 }
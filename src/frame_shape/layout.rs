@@ -0,0 +1,182 @@
+use crate::frame_shape::audio::position_pan;
+use crate::frame_shape::TileRect;
+use serde::Deserialize;
+use std::path::Path;
+
+/// A frame shape described by a `.toml` or `.json` file rather than a `FrameShape` variant.
+///
+/// Each tile is a fractional rectangle of the output frame, `0.0` is the top / left edge and
+/// `1.0` is the bottom / right edge. Tiles are listed in input order, tile 1 takes group 1 etc.
+///
+/// ```toml
+/// [[tile]]
+/// x = 0.0
+/// y = 0.0
+/// w = 0.5
+/// h = 1.0
+/// pan = -0.6  # optional: -1.0 left to 1.0 right, defaults to the tile position
+/// gain = -3.0 # optional: volume change in dB, defaults to 0
+/// role = "side" # optional: name used with `--role`, defaults to "tile"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct Layout {
+    #[serde(rename = "tile", alias = "tiles")]
+    tiles: Vec<LayoutTile>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct LayoutTile {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    pan: Option<f32>,
+    gain: Option<f32>,
+    role: Option<String>,
+}
+
+impl Layout {
+    pub(crate) fn from_file(path: &Path) -> Result<Layout, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read layout file {:?}: {}", path, e))?;
+        let is_json = path.extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+        Self::from_text(&text, is_json).map_err(|e| format!("invalid layout file {:?}: {}", path, e))
+    }
+
+    /// `count` tiles in rows as close to square as possible, rows nearer the bottom take any extra tiles
+    pub(crate) fn balanced_rows(count: u32) -> Layout {
+        let rows = (count as f32).sqrt().round().max(1.0) as u32;
+        let (base, extra) = (count / rows, count % rows);
+        let mut tiles = vec![];
+        for r in 0..rows {
+            let cols = if r >= rows - extra { base + 1 } else { base };
+            for c in 0..cols {
+                tiles.push(LayoutTile {
+                    x: c as f32 / cols as f32,
+                    y: r as f32 / rows as f32,
+                    w: 1.0 / cols as f32,
+                    h: 1.0 / rows as f32,
+                    pan: None,
+                    gain: None,
+                    role: None,
+                });
+            }
+        }
+        Layout { tiles }
+    }
+
+    pub(crate) fn from_text(text: &str, is_json: bool) -> Result<Layout, String> {
+        let mut layout: Layout = if is_json {
+            serde_json::from_str(text).map_err(|e| e.to_string())?
+        } else {
+            toml::from_str(text).map_err(|e| e.to_string())?
+        };
+        layout.validate()?;
+        // `--role` names are lowercased, so are the layout's
+        for t in layout.tiles.iter_mut() {
+            t.role = t.role.as_ref().map(|r| r.trim().to_lowercase());
+        }
+        Ok(layout)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.tiles.is_empty() {
+            return Err("layout must contain at least one tile".to_string());
+        }
+        for (i, t) in self.tiles.iter().enumerate() {
+            let inside = |a: f32| (0.0..=1.0).contains(&a);
+            if !(inside(t.x) && inside(t.y) && t.w > 0.0 && t.h > 0.0
+                && t.x + t.w <= 1.0001 && t.y + t.h <= 1.0001) {
+                return Err(format!("layout tile #{} is outside of the frame: {:?}", i + 1, t));
+            }
+            if let Some(pan) = t.pan {
+                if !(-1.0..=1.0).contains(&pan) {
+                    return Err(format!("layout tile #{} pan must be between -1.0 and 1.0", i + 1));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn count(&self) -> u32 {
+        self.tiles.len() as u32
+    }
+
+    /// pixel rectangles for each tile, edges are rounded so neighbouring tiles share a border
+    pub(crate) fn pixel_rects(&self, width: u32, height: u32) -> Vec<TileRect> {
+        let edge = |f: f32, max: u32| ((f * max as f32).round() as u32).min(max);
+        self.tiles.iter().map(|t| {
+            let (x0, x1) = (edge(t.x, width), edge(t.x + t.w, width));
+            let (y0, y1) = (edge(t.y, height), edge(t.y + t.h, height));
+            TileRect {
+                x: x0.min(width - 1),
+                y: y0.min(height - 1),
+                w: x1.saturating_sub(x0).max(1),
+                h: y1.saturating_sub(y0).max(1),
+            }
+        }).collect()
+    }
+
+    /// role name of each tile, see [FrameShape::tile_roles](crate::frame_shape::FrameShape::tile_roles)
+    pub(crate) fn roles(&self) -> Vec<String> {
+        self.tiles.iter()
+            .map(|t| t.role.clone().unwrap_or("tile".to_string()))
+            .collect()
+    }
+
+    /// `(pan, gain_db)` for each tile, tiles without a pan are placed by their horizontal centre
+    pub(crate) fn audio_mix(&self) -> Vec<(f32, f32)> {
+        self.tiles.iter().map(|t| {
+            let pan = t.pan.unwrap_or(position_pan(t.x + t.w / 2.0));
+            (pan, t.gain.unwrap_or(0.0))
+        }).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THIRDS: &str = "
+        [[tile]]
+        x = 0.0
+        y = 0.0
+        w = 0.3333
+        h = 1.0
+        [[tile]]
+        x = 0.3333
+        y = 0.0
+        w = 0.6667
+        h = 0.5
+        gain = -3.0
+        [[tile]]
+        x = 0.3333
+        y = 0.5
+        w = 0.6667
+        h = 0.5
+        pan = 1.0
+    ";
+
+    #[test]
+    fn layout_rects_share_edges() {
+        let layout = Layout::from_text(THIRDS, false).unwrap();
+        let rects = layout.pixel_rects(1001, 721);
+        assert_eq!(rects[0], TileRect { x: 0, y: 0, w: 334, h: 721 });
+        assert_eq!(rects[1], TileRect { x: 334, y: 0, w: 667, h: 361 });
+        assert_eq!(rects[2], TileRect { x: 334, y: 361, w: 667, h: 360 });
+        let area: u32 = rects.iter().map(|r| r.w * r.h).sum();
+        assert_eq!(area, 1001 * 721);
+    }
+
+    #[test]
+    fn layout_json_and_validation() {
+        let layout = Layout::from_text(r#"{"tiles": [{"x": 0, "y": 0, "w": 1, "h": 1, "gain": -2}]}"#, true).unwrap();
+        assert_eq!(layout.audio_mix(), vec![(0.0, -2.0)]);
+        let roles = Layout::from_text("[[tiles]]\nx = 0\ny = 0\nw = 1\nh = 1\nrole = \"Side\"\n", false).unwrap();
+        assert_eq!(roles.roles(), vec!["side".to_string()]);
+        assert!(Layout::from_text(r#"{"tiles": [{"x": 0.5, "y": 0, "w": 1, "h": 1}]}"#, true).is_err());
+        assert!(Layout::from_text(r#"{"tiles": []}"#, true).is_err());
+    }
+}
@@ -0,0 +1,711 @@
+//! the row interleaving joiner and tile sizes used before [FrameShape::tile_rects], kept as they
+//! were so the tile compositor can be checked against them, see `compositor_matches_row_joiner`
+use std::ops::{Div, Rem, Sub};
+use std::slice::ChunksExact;
+
+use ffmpeg_sidecar::event::OutputVideoFrame;
+
+use crate::frame_shape::FrameShape;
+
+/// width and height of each tile, in the order frames are passed to [row_joiner]
+pub(super) fn tile_sizes(shape: &FrameShape, width: u32, height: u32) -> Vec<(u32, u32)> {
+    match shape {
+        FrameShape::Mono => {
+            vec![
+                (width, height),
+            ]
+        }
+        FrameShape::Dual => {
+            vec![
+                (width / 2, height),
+                (width - width / 2, height),
+            ]
+        }
+        FrameShape::Triple => {
+            let ow = width / 3;
+            vec![
+                (ow, height),
+                (width - ow - ow, height),
+                (ow, height),
+            ]
+        }
+        FrameShape::Quad => {
+            let (w1, h1) = (width / 2, height / 2);
+            let (w2, h2) = (width - w1, height - h1);
+            vec![
+                (w1, h1),
+                (w2, h1),
+                (w1, h2),
+                (w2, h2),
+            ]
+        }
+        FrameShape::VertEmph => {
+            let w23 = width / 3;
+            let h1 = height / 2;
+            let h2 = height - h1;
+            vec![
+                (width - w23 - w23, height),
+                (w23, h1),
+                (w23, h1),
+                (w23, h2),
+                (w23, h2),
+            ]
+        }
+        FrameShape::HorizEmph => {
+            let w23 = width / 3;
+            let h1 = height / 2;
+            vec![
+                (w23, height),
+                (width - w23 - w23, h1),
+                (width - w23 - w23, height - h1),
+                (w23, height),
+            ]
+        }
+        FrameShape::VertEmph2 => {
+            let owx = width.rem(8);
+            let ow = width.sub(owx).div(8);
+
+            vec![
+                (ow * 2 + owx, height),
+                (ow * 3, height / 2),
+                (ow * 3, height / 2),
+                (ow * 3, height - height / 2),
+                (ow * 3, height - height / 2),
+            ]
+        }
+        FrameShape::HorizEmph2 => {
+            let owx = width.rem(4);
+            let ow = width.sub(owx).div(4);
+            vec![
+                (ow, height),
+                (ow * 2 + owx, height / 2),
+                (ow * 2 + owx, height - height / 2),
+                (ow, height),
+            ]
+        }
+        FrameShape::SideVert | FrameShape::SideVert2 => {
+            let owx = width.rem(3);
+            let ow = width.sub(owx).div(3);
+            vec![
+                (ow, height),
+                (ow * 2 + owx, height / 2),
+                (ow * 2 + owx, height - height / 2),
+            ]
+        }
+        FrameShape::CentreEmphVert | FrameShape::CentreEmphVert2 => {
+            let wmid = width / 5 * 3;
+            let w_l = (width - wmid) / 2;
+            let w_r = width - wmid - w_l;
+
+            let wml = wmid / 2;
+            let wmr = wmid - wml;
+
+            let htop = height / 5 * 3;
+            let hbot = height - htop;
+
+            assert_eq!(wmid + w_l + w_r, width);
+            assert_eq!(wml + wmr + w_l + w_r, width);
+            assert_eq!(htop + hbot, height);
+
+            vec![
+                (wmid, htop),
+                (w_l, height),
+                (w_r, height),
+                (wml, hbot),
+                (wmr, hbot),
+            ]
+        }
+        FrameShape::MoreHoriz | FrameShape::MoreHoriz2 => {
+            let wmid = width / 5 * 3;
+            let w_l = (width - wmid) / 2;
+            let w_r = width - wmid - w_l;
+
+            let wml = wmid / 2;
+            let wmr = wmid - wml;
+
+            let h_mid_big = height / 5 * 3;
+            let h_mid_small = height - h_mid_big;
+
+            let h_side_big = height / 3 * 2;
+            let h_side_small = height - h_side_big;
+
+            assert_eq!(wmid + w_l + w_r, width);
+            assert_eq!(wml + wmr + w_l + w_r, width);
+            assert_eq!(h_mid_big + h_mid_small, height);
+
+            vec![
+                (wmid, h_mid_big),
+                (w_l, h_side_big),
+                (w_r, h_side_big),
+                (wml, h_mid_small),
+                (wmr, h_mid_small),
+                (w_l, h_side_small),
+                (w_r, h_side_small),
+            ]
+        }
+        FrameShape::ExtendedLandscape => {
+            let v_1_3_a = height / 3;
+            let v_1_3_b = height / 3;
+            let v_1_3_c = height - v_1_3_a - v_1_3_b;
+
+            let wmid = width / 5 * 3;
+            let w_l = (width - wmid) / 2;
+            let w_r = width - wmid - w_l;
+
+            let wml = wmid / 2;
+            let wmr = wmid - wml;
+
+            let h_mid_big = height / 5 * 3;
+            let h_mid_small = height - h_mid_big;
+
+
+            vec![
+                (wmid, h_mid_big),
+                (wml, h_mid_small),
+                (wmr, h_mid_small),
+                (w_l, v_1_3_a),
+                (w_l, v_1_3_b),
+                (w_l, v_1_3_c),
+                (w_r, v_1_3_a),
+                (w_r, v_1_3_b),
+                (w_r, v_1_3_c),
+            ]
+        }
+        FrameShape::ExtendedLandscape2 => {
+            let horizontal_thirds = width / 3;
+            let horizontal_thirds_mid = width - horizontal_thirds - horizontal_thirds;
+
+            let vertical_top_thirds = height / 3;
+            let vertical_thirds = height - vertical_top_thirds - vertical_top_thirds;
+
+            let wmid = width / 5 * 3;
+            let w_l = (width - wmid) / 2;
+            let w_r = width - wmid - w_l;
+
+            vec![
+                (wmid, vertical_top_thirds + vertical_top_thirds),
+                (horizontal_thirds, vertical_thirds),
+                (horizontal_thirds_mid, vertical_thirds),
+                (horizontal_thirds, vertical_thirds),
+                (w_l, vertical_top_thirds),
+                (w_r, vertical_top_thirds),
+                (w_l, vertical_top_thirds),
+                (w_r, vertical_top_thirds),
+            ]
+        }
+        FrameShape::OffsetVH4x4 => {
+            let horizontal_023 = width / 4;
+            let horizontal_1 = width - horizontal_023 * 3;
+            let height_min = height / 4;
+            let height_max = height - height_min;
+            vec![
+                (horizontal_1, height_min),
+                (horizontal_023, height_min),
+                (horizontal_023, height_min),
+                (horizontal_023, height_min),
+                (horizontal_023, height_max),
+                (horizontal_023, height_max),
+                (horizontal_1, height_max),
+                (horizontal_023, height_max),
+            ]
+        }
+        _ => unreachable!("{:?} was added after the row joiner", shape),
+    }
+}
+
+/// join `frames` by writing each output row from the rows of the tiles it crosses
+pub(super) fn row_joiner(shape: &FrameShape, frames: &[OutputVideoFrame]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut chunks: Vec<ChunksExact<u8>> = frames
+        .iter()
+        .map(|x| x.data.chunks_exact(x.width as usize * 3))
+        .collect();
+    match shape {
+        FrameShape::Mono => {
+            'outter: loop {
+                for chunk in chunks.iter_mut() {
+                    match chunk.next() {
+                        None => break 'outter,
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+            }
+        }
+        FrameShape::Dual | FrameShape::Triple => {
+            'outter: loop {
+                for chunk in chunks.iter_mut() {
+                    match chunk.next() {
+                        None => break 'outter,
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+            }
+        }
+        FrameShape::Quad => {
+            assert_eq!(chunks.len().rem(2), 0);
+            'outter: loop {
+                for chunk in chunks[0..2].iter_mut() {
+                    match chunk.next() {
+                        None => break 'outter,
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+            }
+            'outter: loop {
+                for chunk in chunks[2..4].iter_mut() {
+                    match chunk.next() {
+                        None => break 'outter,
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+            }
+        }
+        FrameShape::VertEmph | FrameShape::VertEmph2 => {
+            'outter: loop {
+                match chunks[1].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+                match chunks[0].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+                match chunks[2].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+            }
+            'outter: loop {
+                match chunks[3].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+                match chunks[0].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+                match chunks[4].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+            }
+        }
+        FrameShape::HorizEmph | FrameShape::HorizEmph2 => {
+            let mut switch: bool = true;
+            'outter: loop {
+                match chunks[0].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+                if switch {
+                    match chunks[1].next() {
+                        None => { switch = false }
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+                // Important note: Do not change this to an else statement
+                // if switch is None !switch must happen
+                if !switch {
+                    match chunks[2].next() {
+                        None => break 'outter,
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+                match chunks[3].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+            }
+        }
+        FrameShape::SideVert => {
+            let mut switch: bool = true;
+            'outter: loop {
+                match chunks[0].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+                if switch {
+                    match chunks[1].next() {
+                        None => { switch = false }
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+                // Important note: Do not change this to an else statement
+                // if switch is None !switch must happen
+                if !switch {
+                    match chunks[2].next() {
+                        None => break 'outter,
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+            }
+        }
+        FrameShape::SideVert2 => {
+            let mut switch: bool = true;
+            'outter: loop {
+                if switch {
+                    match chunks[1].next() {
+                        None => { switch = false }
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+                // Important note: Do not change this to an else statement
+                // if switch is None !switch must happen
+                if !switch {
+                    match chunks[2].next() {
+                        None => break 'outter,
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+                match chunks[0].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+            }
+        }
+        FrameShape::CentreEmphVert => {
+            let mut switch: bool = true;
+            'outter: loop {
+                // this is a full height item, always iter over it
+                match chunks[1].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+                // the top part and bottom part are diferent, once the top has been consumed
+                // we need to use the bottom part
+                if switch {
+                    match chunks[0].next() {
+                        None => { switch = false }
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+                // Important note: Do not change this to an else statement
+                // if switch is None !switch must happen
+                if !switch {
+                    match chunks[3].next() {
+                        None => break 'outter,
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                    match chunks[4].next() {
+                        None => break 'outter,
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+                // this is a full height item, always iter over it
+                match chunks[2].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+            }
+        }
+        FrameShape::CentreEmphVert2 => {
+            let mut switch: bool = true;
+            'outter: loop {
+                // this is a full height item, always iter over it
+                match chunks[1].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+                // the top part and bottom part are diferent, once the top has been consumed
+                // we need to use the bottom part
+                if switch {
+                    match chunks[3].next() {
+                        None => { switch = false }
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                    match chunks[4].next() {
+                        None => { switch = false }
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+                // Important note: Do not change this to an else statement
+                // if switch is None !switch must happen
+                if !switch {
+                    match chunks[0].next() {
+                        None => break 'outter,
+                        Some(ch) => out.extend_from_slice(ch),
+                    }
+                }
+                // this is a full height item, always iter over it
+                match chunks[2].next() {
+                    None => break 'outter,
+                    Some(ch) => out.extend_from_slice(ch),
+                }
+            }
+        }
+        FrameShape::MoreHoriz => {
+            let mut switch1: bool = true;
+            let mut switch2: bool = true;
+            let mut switch3: bool = true;
+            loop {
+                if switch1 {
+                    match chunks[5].next() {
+                        None => switch1 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch1 {
+                    match chunks[1].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+
+                if switch2 {
+                    match chunks[0].next() {
+                        None => switch2 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch2 {
+                    match chunks[3].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                    match chunks[4].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+
+                if switch3 {
+                    match chunks[6].next() {
+                        None => switch3 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch3 {
+                    match chunks[2].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+            }
+        }
+        FrameShape::MoreHoriz2 => {
+            let mut switch1: bool = true;
+            let mut switch2: bool = true;
+            let mut switch3: bool = true;
+            loop {
+                if switch1 {
+                    match chunks[1].next() {
+                        None => switch1 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch1 {
+                    match chunks[5].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+
+                if switch2 {
+                    match chunks[3].next() {
+                        None => switch2 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                    match chunks[4].next() {
+                        None => switch2 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch2 {
+                    match chunks[0].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+
+                if switch3 {
+                    match chunks[2].next() {
+                        None => switch3 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch3 {
+                    match chunks[6].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+            }
+        }
+        FrameShape::ExtendedLandscape => {
+            let mut switch1: u8 = 0u8;
+            let mut switch2: bool = true;
+            let mut switch3: u8 = 0u8;
+
+            loop {
+                if switch1 == 0 {
+                    match chunks[3].next() {
+                        None => switch1 += 1,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if switch1 == 1 {
+                    match chunks[5].next() {
+                        None => switch1 += 1,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if switch1 == 2 {
+                    match chunks[7].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+
+
+                if switch2 {
+                    match chunks[0].next() {
+                        None => switch2 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch2 {
+                    match chunks[1].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                    match chunks[2].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+
+                if switch3 == 0 {
+                    match chunks[4].next() {
+                        None => switch3 += 1,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if switch3 == 1 {
+                    match chunks[6].next() {
+                        None => switch3 += 1,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if switch3 == 2 {
+                    match chunks[8].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+            }
+        }
+        FrameShape::ExtendedLandscape2 => {
+            let mut switch_vert_top1: bool = true;
+            let mut switch_vert_top2: bool = true;
+            let mut switch_vert_base: bool = true;
+
+            loop {
+                if switch_vert_top1 && switch_vert_base {
+                    match chunks[4].next() {
+                        None => switch_vert_top1 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch_vert_top1 && switch_vert_base {
+                    match chunks[6].next() {
+                        None => switch_vert_base = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+
+                if switch_vert_base {
+                    match chunks[0].next() {
+                        None => switch_vert_base = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if switch_vert_top2 && switch_vert_base {
+                    match chunks[5].next() {
+                        None => switch_vert_top2 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch_vert_top2 && switch_vert_base {
+                    match chunks[7].next() {
+                        None => switch_vert_base = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+
+                if !switch_vert_base {
+                    match chunks[1].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                    match chunks[2].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                    match chunks[3].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+            }
+        }
+        FrameShape::OffsetVH4x4 => {
+            let mut switch_1: bool = true;
+            let mut switch_2: bool = true;
+            let mut switch_3: bool = true;
+            let mut switch_4: bool = true;
+
+            loop {
+                if switch_1{
+                    match chunks[4].next() {
+                        None => switch_1 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch_1{
+                    match chunks[2].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if switch_2{
+                    match chunks[0].next() {
+                        None => switch_2 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch_2{
+                    match chunks[6].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if switch_3{
+                    match chunks[1].next() {
+                        None => switch_3 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch_3{
+                    match chunks[7].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if switch_4{
+                    match chunks[5].next() {
+                        None => switch_4 = false,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+                if !switch_4{
+                    match chunks[3].next() {
+                        None => break,
+                        Some(ch) => out.extend_from_slice(ch)
+                    }
+                }
+            }
+        }
+        _ => unreachable!("{:?} was added after the row joiner", shape),
+    }
+    out
+}
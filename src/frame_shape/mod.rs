@@ -1,7 +1,27 @@
 use crate::request_input;
+use std::path::Path;
 
 mod audio;
 mod frame_join;
+mod layout;
+#[cfg(test)]
+mod legacy_join;
+mod roles;
+mod tiles;
+
+pub(crate) use audio::AudioOverrides;
+pub(crate) use frame_join::{bench_joiner, BlitPool};
+pub(crate) use layout::Layout;
+pub(crate) use roles::Binding;
+
+/// position and size of a single tile within the output frame, in pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct TileRect {
+    pub(crate) x: u32,
+    pub(crate) y: u32,
+    pub(crate) w: u32,
+    pub(crate) h: u32,
+}
 
 #[derive(Clone, Debug)]
 pub(crate) enum FrameShape {
@@ -38,6 +58,10 @@ pub(crate) enum FrameShape {
     ExtendedLandscape2,
     /// see [readme_data/frame_shapes_12.svg](../readme_data/frame_shapes_12.svg) for shape ref
     OffsetVH4x4,
+    /// evenly sized `cols` x `rows` grid, filled left to right then top to bottom
+    Grid { rows: u32, cols: u32 },
+    /// tiles loaded from a layout file, see [Layout]
+    Custom(Layout),
 }
 
 static FRAME_SHAPE_MAPPING: [(FrameShape, [&str; 3], &str); 17] = [
@@ -74,6 +98,25 @@ impl FrameShape {
             FrameShape::MoreHoriz | FrameShape::MoreHoriz2 => 7,
             FrameShape::ExtendedLandscape2 | FrameShape::OffsetVH4x4 => 8,
             FrameShape::ExtendedLandscape => 9,
+            FrameShape::Grid { rows, cols } => rows * cols,
+            FrameShape::Custom(layout) => layout.count(),
+        }
+    }
+    /// a shape with `count` tiles, used when a tile is dropped part way through the output
+    pub(crate) fn with_count(count: u32) -> Option<Self> {
+        match count {
+            0 => None,
+            1 => Some(FrameShape::Mono),
+            2 => Some(FrameShape::Dual),
+            3 => Some(FrameShape::Triple),
+            4 => Some(FrameShape::Quad),
+            n => Some(FrameShape::Custom(Layout::balanced_rows(n))),
+        }
+    }
+    pub(crate) fn from_layout_file(path: &Path) -> Self {
+        match Layout::from_file(path) {
+            Ok(layout) => FrameShape::Custom(layout),
+            Err(e) => panic!("{}", e),
         }
     }
     pub(crate) fn from_str_opt(data: Option<String>) -> Self {
@@ -82,6 +125,9 @@ impl FrameShape {
             Some(data) => { data }
         }.to_lowercase();
         let data = data.as_str().trim();
+        if let Some(grid) = data.strip_prefix("grid:") {
+            return Self::grid_from_str(grid);
+        }
         for (fs, x, _) in FRAME_SHAPE_MAPPING.iter() {
             if x.iter().any(|d| data.eq_ignore_ascii_case(d)) {
                 return fs.clone();
@@ -91,6 +137,25 @@ impl FrameShape {
         Self::print_frame_mapping();
         panic!("No match found for split format: {}", data);
     }
+    /// the `--shape` name of this shape, None for shapes read from a layout file
+    pub(crate) fn name(&self) -> Option<String> {
+        match self {
+            FrameShape::Grid { rows, cols } => Some(format!("grid:{}x{}", cols, rows)),
+            FrameShape::Custom(_) => None,
+            _ => FRAME_SHAPE_MAPPING.iter()
+                .find(|(fs, _, _)| std::mem::discriminant(fs) == std::mem::discriminant(self))
+                .map(|(_, names, _)| names[1].to_string()),
+        }
+    }
+    /// parse `<cols>x<rows>`, eg: `4x3` is four columns by three rows
+    fn grid_from_str(data: &str) -> Self {
+        let parsed = data.split_once('x')
+            .and_then(|(c, r)| Some((c.trim().parse::<u32>().ok()?, r.trim().parse::<u32>().ok()?)));
+        match parsed {
+            Some((cols, rows)) if cols > 0 && rows > 0 => FrameShape::Grid { rows, cols },
+            _ => panic!("Grid format should be 'grid:<columns>x<rows>' eg: 'grid:4x3', found: 'grid:{}'", data),
+        }
+    }
     pub(crate) fn print_frame_mapping() {
         println!("FrameShape name mapping, use any of the below names after -s or -shape; \
         the program will ignore case");
@@ -106,5 +171,6 @@ impl FrameShape {
                 names[0], names[1], names[2],
                 info)
         }
+        println!("{:24}  {:2} {:24} {:2} \t eg: grid:4x3", "Grid", "-", "grid:<columns>x<rows>", "-");
     }
 }
@@ -0,0 +1,149 @@
+use crate::frame_shape::FrameShape;
+use crate::helper_functions::MultiPathBuf;
+
+/// tile positions and the folder that fills them, the folder's videos are split across the positions
+pub(crate) type Binding = (Vec<usize>, MultiPathBuf);
+
+fn repeat(role: &str, n: usize) -> Vec<String> {
+    vec![role.to_string(); n]
+}
+
+impl FrameShape {
+    /// role name of each tile, tiles sharing a role are filled from the same folder
+    pub(crate) fn tile_roles(&self) -> Vec<String> {
+        match self {
+            FrameShape::Mono | FrameShape::Dual | FrameShape::Triple | FrameShape::Quad |
+            FrameShape::Grid { .. } => repeat("tile", self.count() as usize),
+            FrameShape::VertEmph | FrameShape::VertEmph2 => {
+                [repeat("hero", 1), repeat("side", 4)].concat()
+            }
+            FrameShape::HorizEmph | FrameShape::HorizEmph2 => {
+                [repeat("side-vertical", 1), repeat("horizontal", 2), repeat("side-vertical", 1)].concat()
+            }
+            FrameShape::SideVert | FrameShape::SideVert2 => {
+                [repeat("side-vertical", 1), repeat("horizontal", 2)].concat()
+            }
+            FrameShape::CentreEmphVert | FrameShape::CentreEmphVert2 => {
+                [repeat("hero", 1), repeat("side-vertical", 2), repeat("small-horizontal", 2)].concat()
+            }
+            FrameShape::MoreHoriz | FrameShape::MoreHoriz2 => {
+                [repeat("hero", 1), repeat("side-vertical", 2), repeat("small-horizontal", 4)].concat()
+            }
+            FrameShape::ExtendedLandscape => {
+                [repeat("hero", 1), repeat("small-horizontal", 2), repeat("side-1", 3), repeat("side-2", 3)].concat()
+            }
+            FrameShape::ExtendedLandscape2 => {
+                [repeat("hero", 1), repeat("bottom-horizontal", 3), repeat("side", 4)].concat()
+            }
+            FrameShape::OffsetVH4x4 => {
+                [repeat("small-horizontal", 4), repeat("vertical", 4)].concat()
+            }
+            FrameShape::Custom(layout) => layout.roles(),
+        }
+    }
+
+    /// unique role names in the order they first appear
+    pub(crate) fn role_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for role in self.tile_roles() {
+            if !names.contains(&role) {
+                names.push(role)
+            }
+        }
+        names
+    }
+
+    /// tile positions of every tile with one of the given roles
+    fn role_positions(&self, roles: &[String]) -> Vec<usize> {
+        self.tile_roles().iter()
+            .enumerate()
+            .filter(|(_, r)| roles.contains(r))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// bind `-f` folders to tiles, checked in order:
+    ///
+    /// * one folder per tile
+    /// * one folder per role, in role order
+    /// * two folders where the shape has vertical roles: the first fills the vertical tiles,
+    ///   the second fills everything else
+    /// * fewer folders than roles: folder n fills role n, the last folder fills the remaining roles
+    pub(crate) fn bind_folders(&self, srcs: Vec<MultiPathBuf>) -> Result<Vec<Binding>, String> {
+        let names = self.role_names();
+        let count = self.count() as usize;
+        let (vertical, other): (Vec<String>, Vec<String>) = names.iter()
+            .cloned()
+            .partition(|r| r.contains("vertical"));
+
+        let role_groups: Vec<Vec<String>> = match srcs.len() {
+            0 => return Err("No folders given".to_string()),
+            k if k == count => return Ok(srcs.into_iter().enumerate().map(|(i, s)| (vec![i], s)).collect()),
+            k if k == names.len() => names.into_iter().map(|r| vec![r]).collect(),
+            2 if !vertical.is_empty() && !other.is_empty() => vec![vertical, other],
+            k if k < names.len() => {
+                let mut groups: Vec<Vec<String>> = names[..k - 1].iter().map(|r| vec![r.clone()]).collect();
+                groups.push(names[k - 1..].to_vec());
+                groups
+            }
+            k => return Err(format!(
+                "{} folders can not be mapped to {:?}, use 1 to {} folders (one per role: {}) or {} folders (one per tile)",
+                k, self, names.len(), names.join(", "), count
+            )),
+        };
+        Ok(role_groups.iter()
+            .map(|roles| self.role_positions(roles))
+            .zip(srcs)
+            .collect())
+    }
+
+    /// bind `--role name=path` inputs to tiles, every role of the shape must be given
+    pub(crate) fn bind_roles(&self, roles: Vec<(String, MultiPathBuf)>) -> Result<Vec<Binding>, String> {
+        let names = self.role_names();
+        if let Some((r, _)) = roles.iter().find(|(r, _)| !names.contains(r)) {
+            return Err(format!("{:?} has no role '{}', roles are: {}", self, r, names.join(", ")));
+        }
+        names.iter().map(|name| {
+            let mut srcs = roles.iter().filter(|(r, _)| r == name).map(|(_, s)| s.clone());
+            let mut src = srcs.next().ok_or(format!("--role {}=<path> is required for {:?}", name, self))?;
+            srcs.for_each(|s| src.extend(s));
+            Ok((self.role_positions(std::slice::from_ref(name)), src))
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn positions(shape: FrameShape, folders: usize) -> Vec<Vec<usize>> {
+        let srcs = (0..folders).map(|i| MultiPathBuf::from_str(&format!("/f{}", i)).unwrap()).collect();
+        shape.bind_folders(srcs).unwrap().into_iter().map(|(p, _)| p).collect()
+    }
+
+    #[test]
+    fn folders_bind_to_roles() {
+        // matches the original hard coded folder splits
+        assert_eq!(positions(FrameShape::VertEmph, 2), vec![vec![0], vec![1, 2, 3, 4]]);
+        assert_eq!(positions(FrameShape::HorizEmph, 2), vec![vec![0, 3], vec![1, 2]]);
+        assert_eq!(positions(FrameShape::CentreEmphVert, 2), vec![vec![1, 2], vec![0, 3, 4]]);
+        assert_eq!(positions(FrameShape::MoreHoriz, 3), vec![vec![0], vec![1, 2], vec![3, 4, 5, 6]]);
+        assert_eq!(positions(FrameShape::ExtendedLandscape, 2), vec![vec![0], (1..9).collect()]);
+        assert_eq!(positions(FrameShape::ExtendedLandscape, 3), vec![vec![0], vec![1, 2], (3..9).collect()]);
+        assert_eq!(positions(FrameShape::OffsetVH4x4, 2), vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]]);
+        // combinations that used to fall back to one folder per tile
+        assert_eq!(positions(FrameShape::MoreHoriz, 2), vec![vec![1, 2], vec![0, 3, 4, 5, 6]]);
+        assert_eq!(positions(FrameShape::Quad, 4), vec![vec![0], vec![1], vec![2], vec![3]]);
+        assert!(FrameShape::Quad.bind_folders(vec![MultiPathBuf::from_str("/a").unwrap(); 3]).is_err());
+
+        let roles = vec![
+            ("side".to_string(), MultiPathBuf::from_str("/side").unwrap()),
+            ("hero".to_string(), MultiPathBuf::from_str("/hero").unwrap()),
+        ];
+        let bound = FrameShape::VertEmph.bind_roles(roles).unwrap();
+        assert_eq!(bound.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>(), vec![vec![0], vec![1, 2, 3, 4]]);
+        let missing = vec![("hero".to_string(), MultiPathBuf::from_str("/hero").unwrap())];
+        assert!(FrameShape::VertEmph.bind_roles(missing).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+use crate::frame_shape::{FrameShape, TileRect};
+use std::ops::{Div, Rem, Sub};
+
+fn rect(x: u32, y: u32, w: u32, h: u32) -> TileRect {
+    TileRect { x, y, w, h }
+}
+
+/// split `length` into `parts`, returns the start and size of each part
+fn grid_split(length: u32, parts: u32) -> (Vec<u32>, Vec<u32>) {
+    let (base, extra) = (length / parts, length % parts);
+    let sizes: Vec<u32> = (0..parts)
+        .map(|i| if i >= parts - extra { base + 1 } else { base })
+        .collect();
+    let starts = sizes.iter()
+        .scan(0, |acc, s| {
+            let start = *acc;
+            *acc += s;
+            Some(start)
+        })
+        .collect();
+    (starts, sizes)
+}
+
+impl FrameShape {
+    /// position and size of every tile for an output frame of `width` x `height`
+    ///
+    /// new frame shapes must be implemented here, tiles are listed in input order
+    /// and should cover the output frame exactly once
+    pub(crate) fn tile_rects(&self, width: u32, height: u32) -> Vec<TileRect> {
+        match self {
+            FrameShape::Mono => {
+                vec![
+                    rect(0, 0, width, height),
+                ]
+            }
+            FrameShape::Dual => {
+                let w1 = width / 2;
+                vec![
+                    rect(0, 0, w1, height),
+                    rect(w1, 0, width - w1, height),
+                ]
+            }
+            FrameShape::Triple => {
+                let ow = width / 3;
+                let w_mid = width - ow - ow;
+                vec![
+                    rect(0, 0, ow, height),
+                    rect(ow, 0, w_mid, height),
+                    rect(ow + w_mid, 0, ow, height),
+                ]
+            }
+            FrameShape::Quad => {
+                let (w1, h1) = (width / 2, height / 2);
+                let (w2, h2) = (width - w1, height - h1);
+                vec![
+                    rect(0, 0, w1, h1),
+                    rect(w1, 0, w2, h1),
+                    rect(0, h1, w1, h2),
+                    rect(w1, h1, w2, h2),
+                ]
+            }
+            FrameShape::VertEmph => {
+                let w23 = width / 3;
+                let w_mid = width - w23 - w23;
+                let h1 = height / 2;
+                let h2 = height - h1;
+                vec![
+                    rect(w23, 0, w_mid, height),
+                    rect(0, 0, w23, h1),
+                    rect(w23 + w_mid, 0, w23, h1),
+                    rect(0, h1, w23, h2),
+                    rect(w23 + w_mid, h1, w23, h2),
+                ]
+            }
+            FrameShape::HorizEmph => {
+                let w23 = width / 3;
+                let w_mid = width - w23 - w23;
+                let h1 = height / 2;
+                vec![
+                    rect(0, 0, w23, height),
+                    rect(w23, 0, w_mid, h1),
+                    rect(w23, h1, w_mid, height - h1),
+                    rect(w23 + w_mid, 0, w23, height),
+                ]
+            }
+            FrameShape::VertEmph2 => {
+                let owx = width.rem(8);
+                let ow = width.sub(owx).div(8);
+                let w_mid = ow * 2 + owx;
+                let h1 = height / 2;
+                vec![
+                    rect(ow * 3, 0, w_mid, height),
+                    rect(0, 0, ow * 3, h1),
+                    rect(ow * 3 + w_mid, 0, ow * 3, h1),
+                    rect(0, h1, ow * 3, height - h1),
+                    rect(ow * 3 + w_mid, h1, ow * 3, height - h1),
+                ]
+            }
+            FrameShape::HorizEmph2 => {
+                let owx = width.rem(4);
+                let ow = width.sub(owx).div(4);
+                let w_mid = ow * 2 + owx;
+                let h1 = height / 2;
+                vec![
+                    rect(0, 0, ow, height),
+                    rect(ow, 0, w_mid, h1),
+                    rect(ow, h1, w_mid, height - h1),
+                    rect(ow + w_mid, 0, ow, height),
+                ]
+            }
+            FrameShape::SideVert => {
+                let owx = width.rem(3);
+                let ow = width.sub(owx).div(3);
+                let h1 = height / 2;
+                vec![
+                    rect(0, 0, ow, height),
+                    rect(ow, 0, ow * 2 + owx, h1),
+                    rect(ow, h1, ow * 2 + owx, height - h1),
+                ]
+            }
+            FrameShape::SideVert2 => {
+                let owx = width.rem(3);
+                let ow = width.sub(owx).div(3);
+                let h1 = height / 2;
+                vec![
+                    rect(ow * 2 + owx, 0, ow, height),
+                    rect(0, 0, ow * 2 + owx, h1),
+                    rect(0, h1, ow * 2 + owx, height - h1),
+                ]
+            }
+            FrameShape::CentreEmphVert | FrameShape::CentreEmphVert2 => {
+                let wmid = width / 5 * 3;
+                let w_l = (width - wmid) / 2;
+                let w_r = width - wmid - w_l;
+
+                let wml = wmid / 2;
+                let wmr = wmid - wml;
+
+                let htop = height / 5 * 3;
+                let hbot = height - htop;
+
+                // the large centre frame is on top for CentreEmphVert, below for CentreEmphVert2
+                let (y_big, y_small) = match self {
+                    FrameShape::CentreEmphVert => (0, htop),
+                    _ => (hbot, 0),
+                };
+                vec![
+                    rect(w_l, y_big, wmid, htop),
+                    rect(0, 0, w_l, height),
+                    rect(w_l + wmid, 0, w_r, height),
+                    rect(w_l, y_small, wml, hbot),
+                    rect(w_l + wml, y_small, wmr, hbot),
+                ]
+            }
+            FrameShape::MoreHoriz | FrameShape::MoreHoriz2 => {
+                let wmid = width / 5 * 3;
+                let w_l = (width - wmid) / 2;
+                let w_r = width - wmid - w_l;
+
+                let wml = wmid / 2;
+                let wmr = wmid - wml;
+
+                let h_mid_big = height / 5 * 3;
+                let h_mid_small = height - h_mid_big;
+
+                let h_side_big = height / 3 * 2;
+                let h_side_small = height - h_side_big;
+
+                // MoreHoriz has the small frames on top, MoreHoriz2 is vertically mirrored
+                let (y_mid_big, y_mid_small, y_side_big, y_side_small) = match self {
+                    FrameShape::MoreHoriz => (0, h_mid_big, h_side_small, 0),
+                    _ => (h_mid_small, 0, 0, h_side_big),
+                };
+                vec![
+                    rect(w_l, y_mid_big, wmid, h_mid_big),
+                    rect(0, y_side_big, w_l, h_side_big),
+                    rect(w_l + wmid, y_side_big, w_r, h_side_big),
+                    rect(w_l, y_mid_small, wml, h_mid_small),
+                    rect(w_l + wml, y_mid_small, wmr, h_mid_small),
+                    rect(0, y_side_small, w_l, h_side_small),
+                    rect(w_l + wmid, y_side_small, w_r, h_side_small),
+                ]
+            }
+            FrameShape::ExtendedLandscape => {
+                let v_1_3_a = height / 3;
+                let v_1_3_b = height / 3;
+                let v_1_3_c = height - v_1_3_a - v_1_3_b;
+
+                let wmid = width / 5 * 3;
+                let w_l = (width - wmid) / 2;
+                let w_r = width - wmid - w_l;
+
+                let wml = wmid / 2;
+                let wmr = wmid - wml;
+
+                let h_mid_big = height / 5 * 3;
+                let h_mid_small = height - h_mid_big;
+
+                // the left column is filled by frames 4, 6, 8 and the right by 5, 7, 9
+                vec![
+                    rect(w_l, 0, wmid, h_mid_big),
+                    rect(w_l, h_mid_big, wml, h_mid_small),
+                    rect(w_l + wml, h_mid_big, wmr, h_mid_small),
+                    rect(0, 0, w_l, v_1_3_a),
+                    rect(w_l + wmid, 0, w_r, v_1_3_b),
+                    rect(0, v_1_3_a, w_l, v_1_3_c),
+                    rect(w_l + wmid, v_1_3_b, w_r, v_1_3_a),
+                    rect(0, v_1_3_a + v_1_3_c, w_l, v_1_3_b),
+                    rect(w_l + wmid, v_1_3_b + v_1_3_a, w_r, v_1_3_c),
+                ]
+            }
+            FrameShape::ExtendedLandscape2 => {
+                let horizontal_thirds = width / 3;
+                let horizontal_thirds_mid = width - horizontal_thirds - horizontal_thirds;
+
+                let vertical_top_thirds = height / 3;
+                let vertical_thirds = height - vertical_top_thirds - vertical_top_thirds;
+
+                let wmid = width / 5 * 3;
+                let w_l = (width - wmid) / 2;
+                let w_r = width - wmid - w_l;
+
+                let h_top = vertical_top_thirds + vertical_top_thirds;
+                vec![
+                    rect(w_l, 0, wmid, h_top),
+                    rect(0, h_top, horizontal_thirds, vertical_thirds),
+                    rect(horizontal_thirds, h_top, horizontal_thirds_mid, vertical_thirds),
+                    rect(horizontal_thirds + horizontal_thirds_mid, h_top, horizontal_thirds, vertical_thirds),
+                    rect(0, 0, w_l, vertical_top_thirds),
+                    rect(w_l + wmid, 0, w_r, vertical_top_thirds),
+                    rect(0, vertical_top_thirds, w_l, vertical_top_thirds),
+                    rect(w_l + wmid, vertical_top_thirds, w_r, vertical_top_thirds),
+                ]
+            }
+            FrameShape::OffsetVH4x4 => {
+                let horizontal_023 = width / 4;
+                let horizontal_1 = width - horizontal_023 * 3;
+                let height_min = height / 4;
+                let height_max = height - height_min;
+
+                let x_col3 = horizontal_023 + horizontal_1;
+                let x_col4 = x_col3 + horizontal_023;
+                vec![
+                    rect(horizontal_023, 0, horizontal_1, height_min),
+                    rect(x_col3, 0, horizontal_023, height_min),
+                    rect(0, height_max, horizontal_023, height_min),
+                    rect(x_col4, height_max, horizontal_023, height_min),
+                    rect(0, 0, horizontal_023, height_max),
+                    rect(x_col4, 0, horizontal_023, height_max),
+                    rect(horizontal_023, height_min, horizontal_1, height_max),
+                    rect(x_col3, height_min, horizontal_023, height_max),
+                ]
+            }
+            FrameShape::Grid { rows, cols } => {
+                // remainder pixels go to the last columns / rows, matching Quad
+                let (col_x, col_w) = grid_split(width, *cols);
+                let (row_y, row_h) = grid_split(height, *rows);
+                let mut rects = Vec::with_capacity((rows * cols) as usize);
+                for r in 0..*rows as usize {
+                    for c in 0..*cols as usize {
+                        rects.push(rect(col_x[c], row_y[r], col_w[c], row_h[r]));
+                    }
+                }
+                rects
+            }
+            FrameShape::Custom(layout) => layout.pixel_rects(width, height),
+        }
+    }
+}
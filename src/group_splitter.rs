@@ -146,6 +146,51 @@ pub fn regrouper(v_list: Vec<Video>, group_count: usize) -> Vec<Vec<Video>> {
 }
 
 
+/// pick the videos whose lengths add up to the smallest total of at least `target_millis`,
+/// the last picked video is trimmed to end exactly on the target. order is kept.
+///
+/// if every video together is shorter than the target all videos are returned
+pub fn select_for_duration(mut v_list: Vec<Video>, target_millis: i64) -> Vec<Video> {
+    let millis: Vec<usize> = v_list.iter_mut()
+        .map(|v| v.get_length().unwrap_or(0).max(0) as usize)
+        .collect();
+    let target_millis = target_millis.max(0) as usize;
+    if millis.iter().sum::<usize>() <= target_millis {
+        return v_list;
+    }
+    // lengths in whole seconds keep the table small for long targets, rounded down
+    // so any total of at least the target in seconds is at least as long in milliseconds
+    let lengths: Vec<usize> = millis.iter().map(|m| m / 1000).collect();
+    let target = target_millis.div_ceil(1000);
+    let limit = target + lengths.iter().max().unwrap();
+
+    // reached_by[s] is the first video that made a total of `s` seconds possible
+    let mut reached_by: Vec<Option<usize>> = vec![None; limit + 1];
+    let mut reachable = vec![false; limit + 1];
+    reachable[0] = true;
+    for (i, &len) in lengths.iter().enumerate().filter(|(_, &l)| l > 0) {
+        for s in (len..=limit).rev() {
+            if reachable[s - len] && !reachable[s] {
+                reachable[s] = true;
+                reached_by[s] = Some(i);
+            }
+        }
+    }
+    let mut total = match (target..=limit).find(|&s| reachable[s]) {
+        Some(total) => total,
+        None => return v_list,
+    };
+
+    let mut picked = vec![false; v_list.len()];
+    while let Some(i) = reached_by[total] {
+        picked[i] = true;
+        total -= lengths[i];
+    }
+    let mut picked = picked.into_iter();
+    v_list.retain(|_| picked.next().unwrap());
+    v_list
+}
+
 fn generalized_regrouper(items: Vec<LenV>, group_count: usize) -> ReGroupStatus {
     match partition_into_groups(&items, group_count, 10) {
         Some(pargrp) => {
@@ -252,4 +297,34 @@ fn backtrack<'a>(
     }
 
     false
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn videos(lengths: &[i64]) -> Vec<Video> {
+        lengths.iter().enumerate().map(|(i, l)| {
+            let mut v = Video::from_path(format!("{}.mp4", i));
+            v.length_millis = Some(*l);
+            v
+        }).collect()
+    }
+
+    #[test]
+    fn duration_selection_is_closest_over_target() {
+        let picked = select_for_duration(videos(&[50_000, 20_000, 35_000, 10_000, 40_000]), 60_000);
+        let lengths: Vec<i64> = picked.iter().map(|v| v.length_millis.unwrap()).collect();
+        assert_eq!(lengths.iter().sum::<i64>(), 60_000);
+        assert_eq!(lengths, vec![50_000, 10_000]);
+
+        // rounded up 10.2 + 10.2 + 9.1 would reach 30s while they only play for 29.5s
+        let picked = select_for_duration(videos(&[10_200, 10_200, 9_100, 12_000]), 30_000);
+        let total: i64 = picked.iter().map(|v| v.length_millis.unwrap()).sum();
+        assert!(picked.len() < 4 && total >= 30_000, "{} videos, {}ms", picked.len(), total);
+
+        // too short to reach the target, everything is kept
+        assert_eq!(select_for_duration(videos(&[10_000, 20_000]), 60_000).len(), 2);
+    }
+}
@@ -1,20 +1,26 @@
 use std::fmt::{Debug, Formatter};
 
-use crate::{group_split, group_splitter};
-use crate::frame_shape::FrameShape;
+use crate::{group_split, group_splitter, playlist};
+use crate::playlist::Trim;
+use crate::frame_shape::{FrameShape, TileRect};
 use crate::video::Video;
 use ffmpeg_sidecar::child::FfmpegChild;
 use ffmpeg_sidecar::event::{FfmpegEvent, LogLevel};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time;
 
 
-#[cfg(target_os = "windows")]
+/// joins several folders into a single input, the same on every OS as `|` is not used in paths
 const PATH_DELIMITER: char = '|';
 
-#[cfg(not(target_os = "windows"))]
-const PATH_DELIMITER: char = '/';
+/// characters that mark an input path as a glob pattern, eg: `/clips/**/*.mp4`
+const GLOB_CHARS: [char; 3] = ['*', '?', '['];
+
+/// files found in folders and glob patterns are only used with one of these extensions unless `--include` is given
+const VIDEO_EXTENSIONS: [&str; 15] = [
+    "mp4", "m4v", "mkv", "mov", "avi", "webm", "wmv", "flv", "mpg", "mpeg", "ts", "mts", "m2ts", "3gp", "ogv",
+];
 
 pub fn iter_ffmpeg_events(child: &mut FfmpegChild) {
     for i in child.iter().unwrap() {
@@ -83,57 +89,211 @@ pub fn seconds_to_hhmmss(sec: u64) -> String {
     format!("{:02}-{:02}-{:02}", hour, min, sec)
 }
 
+/// parse `hh:mm:ss`, `mm:ss` or plain seconds, fractional seconds are allowed, eg: `00:12:30.5`
+pub fn hhmmss_to_seconds(time: &str) -> Result<f64, String> {
+    let parts: Vec<&str> = time.trim().split(':').collect();
+    if parts.len() > 3 {
+        return Err(format!("'{}' is not a valid time, use hh:mm:ss", time));
+    }
+    parts.iter().try_fold(0f64, |acc, p| {
+        match p.trim().parse::<f64>() {
+            Ok(v) if v >= 0.0 => Ok(acc * 60.0 + v),
+            _ => Err(format!("'{}' is not a valid time, use hh:mm:ss", time)),
+        }
+    })
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct MultiPathBuf {
     inner: Vec<PathBuf>,
+    filter: ScanFilter,
+}
+
+/// which files found in folders and glob patterns are used, files named directly or listed
+/// in a playlist are always used
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ScanFilter {
+    /// also scan sub folders
+    recursive: bool,
+    /// matched against the whole path ignoring case, [VIDEO_EXTENSIONS] for folders when empty
+    include: Vec<glob::Pattern>,
+    exclude: Vec<glob::Pattern>,
+}
+
+impl ScanFilter {
+    /// `include` and `exclude` are comma separated lists of glob patterns, eg: `*.mp4,*.mkv`
+    pub(crate) fn new(recursive: bool, include: &[String], exclude: &[String]) -> Result<ScanFilter, String> {
+        let patterns = |list: &[String]| -> Result<Vec<glob::Pattern>, String> {
+            list.iter()
+                .flat_map(|l| l.split(','))
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(|p| glob::Pattern::new(p).map_err(|e| format!("Invalid glob pattern '{}': {}", p, e)))
+                .collect()
+        };
+        Ok(ScanFilter { recursive, include: patterns(include)?, exclude: patterns(exclude)? })
+    }
+
+    /// why a scanned file is not used, None if it is. a glob pattern already names the files
+    /// wanted, so the default extensions only apply to files found in folders
+    fn skip_reason(&self, path: &Path, in_folder: bool) -> Option<&'static str> {
+        let options = glob::MatchOptions { case_sensitive: false, ..Default::default() };
+        let hidden = path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.'));
+        let included = match (self.include.is_empty(), in_folder) {
+            (true, true) => path.extension()
+                .is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str())),
+            (true, false) => true,
+            (false, _) => self.include.iter().any(|p| p.matches_path_with(path, options)),
+        };
+        if hidden {
+            Some("hidden")
+        } else if !included {
+            Some("not included")
+        } else if self.exclude.iter().any(|p| p.matches_path_with(path, options)) {
+            Some("excluded")
+        } else {
+            None
+        }
+    }
 }
 
 impl FromStr for MultiPathBuf {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let data = if s.contains(PATH_DELIMITER) {
-            s.split(PATH_DELIMITER).map(|f| { PathBuf::from(f) }).collect()
-        } else {
-            vec![PathBuf::from(s)]
-        };
+        let data: Vec<PathBuf> = s.split(PATH_DELIMITER)
+            .map(|f| f.trim())
+            .filter(|f| !f.is_empty())
+            .map(PathBuf::from)
+            .collect();
+        if data.is_empty() {
+            return Err(format!("No paths found in '{}'", s));
+        }
+        for f in data.iter().filter(|f| is_glob(f)) {
+            if let Err(e) = glob::Pattern::new(&f.to_string_lossy()) {
+                return Err(format!("Invalid glob pattern '{}': {}", f.display(), e));
+            }
+        }
         Ok(
             MultiPathBuf {
                 inner: data,
+                filter: ScanFilter::default(),
             }
         )
     }
 }
 
+/// paths that exist are used as they are, so folders and files named eg: `Trip [2024]` are not patterns
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(GLOB_CHARS) && !path.exists()
+}
+
 impl MultiPathBuf {
-    pub fn read_dir(&self) -> Vec<PathBuf> {
+    /// add the paths from `other` to this input
+    pub fn extend(&mut self, other: MultiPathBuf) {
+        self.inner.extend(other.inner)
+    }
+    /// relative paths joined to `base`
+    pub(crate) fn resolved(&self, base: &Path) -> MultiPathBuf {
+        MultiPathBuf { inner: self.inner.iter().map(|p| base.join(p)).collect(), filter: self.filter.clone() }
+    }
+
+    /// scan folders and glob patterns with `filter`
+    pub(crate) fn with_filter(mut self, filter: ScanFilter) -> MultiPathBuf {
+        self.filter = filter;
+        self
+    }
+
+    /// every clip of this input with the part to play, playlists are expanded in the order listed.
+    /// files skipped by the [ScanFilter] are counted in a summary
+    pub(crate) fn entries(&self) -> Vec<playlist::Entry> {
         let mut out = vec![];
+        let mut scanned = vec![];
         for f in self.inner.iter(){
+            if is_glob(f) {
+                // patterns are checked in from_str, unreadable matches are skipped
+                let mut matches: Vec<PathBuf> = glob::glob(&f.to_string_lossy())
+                    .unwrap()
+                    .filter_map(|p| p.ok())
+                    .filter(|p| p.is_file())
+                    .collect();
+                matches.sort();
+                scanned.extend(matches.into_iter().map(|p| (p, false)));
+                continue
+            }
+            if f.is_file() && playlist::is_playlist(f) {
+                let entries = playlist::read(f).unwrap_or_else(|e| panic!("{}", e));
+                for (p, _) in entries.iter().filter(|(p, _)| !p.is_file()) {
+                    println!("Playlist entry not found: {:?}", p);
+                }
+                out.extend(entries);
+                continue
+            }
             if f.is_file() {
-                out.push(f.clone());
+                out.push((f.clone(), Trim::default()));
                 continue
             }
-            let o1 =  f.read_dir()
-                .unwrap()
-                .into_iter()
-                .map(|p| p.unwrap().path());
-            out.extend(o1)
+            scanned.extend(files_in(f, self.filter.recursive).into_iter().map(|p| (p, true)));
+        }
+
+        let mut skipped: Vec<(&str, usize)> = vec![];
+        for (p, in_folder) in scanned {
+            match self.filter.skip_reason(&p, in_folder) {
+                None => out.push((p, Trim::default())),
+                Some(reason) => match skipped.iter_mut().find(|(r, _)| *r == reason) {
+                    Some((_, n)) => *n += 1,
+                    None => skipped.push((reason, 1)),
+                },
+            }
+        }
+        if !skipped.is_empty() {
+            let counts: Vec<String> = skipped.iter().map(|(r, n)| format!("{} {}", n, r)).collect();
+            println!("Skipped files in {}: {}", self, counts.join(", "));
         }
         out
     }
     fn is_dir(&self) -> bool {
-        self.inner.iter().all(|a| a.is_dir() | a.is_file())
+        self.inner.iter().all(|a| a.is_dir() | a.is_file() | is_glob(a))
+    }
+}
+
+impl std::fmt::Display for MultiPathBuf {
+    /// the paths separated by `|`, as accepted by [MultiPathBuf::from_str]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let paths: Vec<_> = self.inner.iter().map(|p| p.to_string_lossy()).collect();
+        write!(f, "{}", paths.join(&PATH_DELIMITER.to_string()))
     }
 }
 
 impl Into<MultiPathBuf> for &MultiPathBuf {
     fn into(self) -> MultiPathBuf {
         MultiPathBuf {
-            inner: self.inner.clone()
+            inner: self.inner.clone(),
+            filter: self.filter.clone(),
         }
     }
 }
 
+/// the files in `dir` sorted by path, sub folders are walked when `recursive` is set
+fn files_in(dir: &Path, recursive: bool) -> Vec<PathBuf> {
+    let mut out = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(d) = dirs.pop() {
+        let entries = d.read_dir().unwrap_or_else(|e| panic!("failed to read folder {:?}: {}", d, e));
+        for p in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if p.is_dir() {
+                if recursive {
+                    dirs.push(p);
+                }
+            } else {
+                out.push(p);
+            }
+        }
+    }
+    out.sort();
+    out
+}
+
 
 #[cfg(feature = "hyperDebug")]
 #[inline]
@@ -148,6 +308,77 @@ mod tests {
     use std::thread;
     use std::time::Duration;
 
+    #[test]
+    fn multi_path_buf_groups_and_globs() {
+        let mpb = MultiPathBuf::from_str("/home/me/clips").unwrap();
+        assert_eq!(mpb.inner, vec![PathBuf::from("/home/me/clips")]);
+
+        let mpb = MultiPathBuf::from_str("/home/me/left | /mnt/nas/left|").unwrap();
+        assert_eq!(mpb.inner, vec![PathBuf::from("/home/me/left"), PathBuf::from("/mnt/nas/left")]);
+        assert!(MultiPathBuf::from_str(" | ").is_err());
+        assert!(MultiPathBuf::from_str("/clips/[.mp4").is_err());
+
+        let dir = std::env::temp_dir().join(format!("video_joiner_glob_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        for f in ["b.mp4", "a.mp4", "notes.txt", "sub/c.mp4"] {
+            std::fs::write(dir.join(f), b"").unwrap();
+        }
+        let pattern = format!("{}|{}", dir.join("*.mp4").display(), dir.join("**/c.mp4").display());
+        let found: Vec<PathBuf> = MultiPathBuf::from_str(&pattern).unwrap().entries().into_iter().map(|(p, _)| p).collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(found, vec![dir.join("a.mp4"), dir.join("b.mp4"), dir.join("sub/c.mp4")]);
+
+        // existing folders with pattern characters in their name are folders, not patterns
+        let dir = std::env::temp_dir().join(format!("video_joiner_brackets_{}", std::process::id()));
+        for d in ["Trip [2024]", "Odd [name"] {
+            std::fs::create_dir_all(dir.join(d)).unwrap();
+            std::fs::write(dir.join(d).join("a.mp4"), b"").unwrap();
+        }
+        let found = |d: &str| -> Vec<PathBuf> {
+            let src = MultiPathBuf::from_str(dir.join(d).to_str().unwrap()).unwrap();
+            src.entries().into_iter().map(|(p, _)| p).collect()
+        };
+        let (trip, odd) = (found("Trip [2024]"), found("Odd [name"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(trip, vec![dir.join("Trip [2024]/a.mp4")]);
+        assert_eq!(odd, vec![dir.join("Odd [name/a.mp4")]);
+    }
+
+    #[test]
+    fn scan_filter_recursive_include_exclude() {
+        let dir = std::env::temp_dir().join(format!("video_joiner_scan_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        for f in ["a.mp4", "b.MKV", "notes.txt", "cover.jpg", ".DS_Store", "sub/c.mp4", "sub/d_proxy.mp4"] {
+            std::fs::write(dir.join(f), b"").unwrap();
+        }
+        let scan = |filter: ScanFilter| -> Vec<PathBuf> {
+            let src = MultiPathBuf::from_str(dir.to_str().unwrap()).unwrap().with_filter(filter);
+            src.entries().into_iter().map(|(p, _)| p).collect()
+        };
+        let defaults = scan(ScanFilter::default());
+        let filtered = scan(ScanFilter::new(true, &["*.mp4, *.jpg".to_string()], &["*_PROXY*".to_string()]).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(defaults, vec![dir.join("a.mp4"), dir.join("b.MKV")]);
+        assert_eq!(filtered, vec![dir.join("a.mp4"), dir.join("cover.jpg"), dir.join("sub/c.mp4")]);
+        assert!(ScanFilter::new(false, &[], &["[".to_string()]).is_err());
+
+        // a glob pattern picks its own files, only --exclude and hidden files still apply
+        let filter = ScanFilter::new(false, &[], &["*_proxy*".to_string()]).unwrap();
+        assert_eq!(filter.skip_reason(Path::new("/clips/a.mxf"), false), None);
+        assert_eq!(filter.skip_reason(Path::new("/clips/a.mxf"), true), Some("not included"));
+        assert_eq!(filter.skip_reason(Path::new("/clips/a_proxy.mxf"), false), Some("excluded"));
+    }
+
+    #[test]
+    fn hhmmss_parsing() {
+        assert_eq!(hhmmss_to_seconds("00:12:30"), Ok(750.0));
+        assert_eq!(hhmmss_to_seconds("12:30.5"), Ok(750.5));
+        assert_eq!(hhmmss_to_seconds("90"), Ok(90.0));
+        assert!(hhmmss_to_seconds("1:2:3:4").is_err());
+        assert!(hhmmss_to_seconds("12:-1").is_err());
+    }
+
     #[test]
     fn frame_timer_counter_check() {
         let mut ft = FrameTimer::new();
@@ -175,32 +406,40 @@ fn parse_debug(text: &str, f: &str, l: u32) {}
 
 fn scan_dir_for_videos_with_len(dir: impl Into<MultiPathBuf>) -> Vec<(i64, Video)> {
     let mut all_videos = Vec::new();
-    for i in dir.into().read_dir() {
-        if i.is_file() {
-            // setup vid items
-            let mut vd = Video::from_path(i.as_path());
-            let le = match vd.get_length() {
-                Ok(le) => le,
-                Err(_) => {
-                    println!("Failed to get video Info for: {:?}", vd.src);
-                    continue;
-                }
-            };
-            all_videos.push((le, vd))
-        }
+    for mut vd in scan_dir_for_videos(dir) {
+        let le = match vd.get_length() {
+            Ok(le) => le,
+            Err(_) => {
+                println!("Failed to get video Info for: {:?}", vd.src);
+                continue;
+            }
+        };
+        all_videos.push((le, vd))
     }
     all_videos
 }
 
+/// every clip of `dir` with a video stream, anything ffprobe finds no video in is listed and left out
 pub fn scan_dir_for_videos(dir: impl Into<MultiPathBuf>) -> Vec<Video> {
+    let dir = dir.into();
     let mut all_videos = Vec::new();
-    for i in dir.into().read_dir() {
+    let mut skipped = Vec::new();
+    for (i, trim) in dir.entries() {
         if i.is_file() {
             // setup vid items
-            let vd = Video::from_path(i.as_path());
-            all_videos.push(vd);
+            let mut vd = Video::from_path(i.as_path()).with_trim(trim);
+            match vd.get_display_size() {
+                Ok(_) => all_videos.push(vd),
+                Err(e) => skipped.push(format!("{:?}: {}", i, e)),
+            }
         };
     }
+    if !skipped.is_empty() {
+        println!("Skipped {} files without a video stream in {}:", skipped.len(), dir);
+        for s in skipped {
+            println!("\t{}", s);
+        }
+    }
     all_videos
 }
 
@@ -216,9 +455,50 @@ pub fn video_group_swap_n(src: impl Into<MultiPathBuf>, groups:usize) -> Vec<Vec
     let src = src.into();
     assert!(src.is_dir(), "Given Input Directory Does Not Exist"); // not my fault
     let all_videos = scan_dir_for_videos_with_len(src);
-    let list_grp = group_splitter::regrouper(
-        all_videos.into_iter().map(|(_,b)|b).collect(),
-        groups);
+    regroup_n(all_videos.into_iter().map(|(_, b)| b).collect(), groups)
+}
+
+/// split a single folder across the tiles by clip orientation, portrait clips fill the tall tiles
+/// and landscape clips fill the wide tiles, each set is then balanced by length.
+///
+/// falls back to [video_group_swap_n] behaviour when the shape has only one kind of tile
+/// or there are not enough clips of an orientation to fill its tiles
+pub fn video_group_orient(src: impl Into<MultiPathBuf>, tiles: &[TileRect]) -> Vec<Vec<Video>> {
+    let src = src.into();
+    assert!(src.is_dir(), "Given Input Directory Does Not Exist"); // not my fault
+    let all_videos: Vec<Video> = scan_dir_for_videos_with_len(src).into_iter().map(|(_, b)| b).collect();
+
+    let (tall, wide): (Vec<usize>, Vec<usize>) = (0..tiles.len()).partition(|&i| tiles[i].h > tiles[i].w);
+    if tall.is_empty() || wide.is_empty() {
+        return regroup_n(all_videos, tiles.len());
+    }
+
+    let (mut portrait, mut landscape) = (vec![], vec![]);
+    for mut v in all_videos {
+        if v.is_portrait() { portrait.push(v) } else { landscape.push(v) }
+    }
+    println!("Orientation: {} portrait clips for tiles {:?}, {} landscape clips for tiles {:?}",
+             portrait.len(), tall.iter().map(|i| i + 1).collect::<Vec<_>>(),
+             landscape.len(), wide.iter().map(|i| i + 1).collect::<Vec<_>>());
+
+    if portrait.len() < tall.len() || landscape.len() < wide.len() {
+        println!("Not enough clips of each orientation, grouping without orientation");
+        let all_videos = portrait.into_iter().chain(landscape).collect();
+        return regroup_n(all_videos, tiles.len());
+    }
+
+    let mut out: Vec<Vec<Video>> = (0..tiles.len()).map(|_| vec![]).collect();
+    for (positions, videos) in [(tall, portrait), (wide, landscape)] {
+        let groups = positions.len();
+        for (pos, grp) in positions.into_iter().zip(regroup_n(videos, groups)) {
+            out[pos] = grp;
+        }
+    }
+    out
+}
+
+fn regroup_n(all_videos: Vec<Video>, groups: usize) -> Vec<Vec<Video>> {
+    let list_grp = group_splitter::regrouper(all_videos, groups);
     print!("groups started: ");
     for  vgroup in list_grp.iter(){
         let mut grplen = 0;
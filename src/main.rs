@@ -1,9 +1,13 @@
-use crate::helper_functions::MultiPathBuf;
-use crate::switches::SortOrder;
+use crate::helper_functions::{hhmmss_to_seconds, MultiPathBuf, ScanFilter};
+use crate::switches::{AudioFocus, AudioMode, AudioTracks, FitMode, OnExhaust, SortOrder};
+use crate::plan::Plan;
+use crate::project::{Project, Resolved};
 use crate::video::{VideoEditData, VideoGroup};
-use clap::{arg, Parser};
-use frame_shape::FrameShape;
+use clap::{arg, CommandFactory, FromArgMatches, Parser, Subcommand};
+use frame_shape::{AudioOverrides, FrameShape};
 use ini::Ini;
+use std::collections::HashMap;
+use std::ops::Not;
 use std::path::PathBuf;
 use std::process::exit;
 use std::str::FromStr;
@@ -12,14 +16,51 @@ pub(crate) mod group_split;
 pub(crate) mod helper_functions;
 pub(crate) mod video;
 pub(crate) mod audio;
+mod decoder;
+mod encoder;
+mod focus;
+mod plan;
+mod probe;
+mod playlist;
+mod project;
 mod switches;
+mod timeline;
 mod group_splitter;
 mod frame_shape;
 
 pub fn main() {
-    let args = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let mut args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Some(path) = &args.project {
+        let project = Project::load(path).unwrap_or_else(|e| panic!("--project {}", e));
+        project.apply(&mut args, &matches);
+    }
+    let plan = match &args.command {
+        Some(Command::Render { plan }) => {
+            let plan = Plan::load(plan).unwrap_or_else(|e| panic!("render --plan {}", e));
+            // the plan's tiles belong to its shape
+            args.split_format = plan.shape.clone();
+            args.layout_file = plan.layout_file.clone();
+            Some(plan)
+        }
+        _ => None,
+    };
+    if let Some(frames) = args.bench_joiner {
+        frame_shape::bench_joiner(args.output_width.unwrap_or(2560), args.output_height.unwrap_or(1440), frames);
+        exit(0)
+    }
 
-    let (mut vid, audio, encoder_args, print_time_only) = run_from_cli(args);
+    if !args.no_cache {
+        probe::enable_cache();
+    }
+    let (mut vid, audio, encoder_args, print_time_only, preview) = run_from_cli(args, plan);
+    if let Some((time, out)) = preview {
+        match vid.render_preview(time, &out) {
+            Ok(_) => println!("Preview written to {:?}", out),
+            Err(e) => panic!("Preview failed: {}", e),
+        }
+        exit(0)
+    }
     match print_time_only {
         (_, true) => {
         vid.print_time(true);
@@ -28,7 +69,10 @@ pub fn main() {
         vid.print_time(false);
         exit(0)}
         _ => {
-            vid.main_loop(audio, encoder_args);
+            if let Err(e) = vid.main_loop(audio, encoder_args) {
+                eprintln!("Error: {}", e);
+                exit(1)
+            }
             println!("------------------------------------------");
             println!("--------------Video Complete--------------");
             println!("------------------------------------------");
@@ -44,9 +88,38 @@ struct Cli {
     /// - Example to automatically split items into groups: -f "C:\Users\username\Videos\videos_to_join\"
     /// - Example to use folder as a group: -f "D:\videos\left" -f "D:\videos\mid" -f "D:\videos\right"
     /// - Example to use more than one folder for a single input: -f "D:\videos\left1|D:\videos\left2"
+    /// - Example using a glob pattern: -f "/home/me/clips/**/*.mp4"
+    /// - Example using a playlist (.m3u, .m3u8, .txt or .csv): -f "D:\exports\left.m3u8"
     #[arg(short = 'f', long = "folder", action=clap::ArgAction::Append, verbatim_doc_comment)]
     input_folder: Vec<MultiPathBuf>,
 
+    /// Add a folder, file or glob pattern to a numbered group, groups start at 1;
+    /// repeat to merge several sources into a single group.
+    /// - Example: --group 1=/clips/left --group 1=/mnt/nas/left --group 2="/clips/right/*.mp4"
+    #[arg(long = "group", action=clap::ArgAction::Append, verbatim_doc_comment, conflicts_with = "input_folder")]
+    group: Vec<String>,
+
+    /// Fill every tile of a role from a folder, file or glob pattern, repeat for each role of the shape;
+    /// tiles sharing a role split the videos between them. see README.md for the roles of each shape
+    /// - Example: -s VertEmph --role hero=/clips/vertical --role side=/clips/horizontal
+    #[arg(long = "role", action=clap::ArgAction::Append, verbatim_doc_comment, conflicts_with_all = ["input_folder", "group"])]
+    role: Vec<String>,
+
+    /// also scan the sub folders of every folder given
+    #[arg(long = "recursive", action)]
+    recursive: bool,
+
+    /// only use scanned files matching one of these comma separated glob patterns, can be repeated
+    /// [default: common video extensions]
+    /// - Example: --include "*.mp4,*.mkv"
+    #[arg(long = "include", action=clap::ArgAction::Append, verbatim_doc_comment)]
+    include: Vec<String>,
+
+    /// leave out scanned files matching one of these comma separated glob patterns, can be repeated
+    /// - Example: --exclude "*_proxy*"
+    #[arg(long = "exclude", action=clap::ArgAction::Append, verbatim_doc_comment)]
+    exclude: Vec<String>,
+
     /// Target *file* to output joined video (requires extension)
     /// if unused a request will be given via text input;
     /// - Example: C:\Users\username\Videos\example.mp4
@@ -55,10 +128,17 @@ struct Cli {
 
     /// Shape Format: 'Double' 'Triple' 'Quad' 'VertEmph' 'HorizEmph'
     /// see README.md for more layouts and layout diagrams
+    /// - Example of an evenly sized grid, 4 columns by 3 rows: -s grid:4x3
     /// if unused a request will be given via text input
     #[arg(short = 's', long = "shape", verbatim_doc_comment)]
     split_format: Option<String>,
 
+    /// Layout file (.toml or .json) describing each tile as a fractional rectangle,
+    /// with optional per-tile audio pan and gain; used in place of --shape
+    /// see README.md for the file format
+    #[arg(long = "layout-file", verbatim_doc_comment, conflicts_with = "split_format")]
+    layout_file: Option<PathBuf>,
+
     /// Output height in pixels [default:1440]
     #[arg(short = 'y', long = "height")]
     output_height: Option<u32>,
@@ -67,6 +147,14 @@ struct Cli {
     #[arg(short = 'x', long = "width")]
     output_width: Option<u32>,
 
+    /// How clips are fitted into their tile, repeat with a group number to set a single group:
+    ///     "crop" scale to fill the tile and crop the overflow (default)
+    ///     "pad" or "pad:<colour>" letterbox, eg: pad:#202020 [default colour: black]
+    ///     "blur" letterbox with a blurred, zoomed copy of the clip
+    /// - Example: --fit blur --fit 1=crop
+    #[arg(long = "fit", action=clap::ArgAction::Append, verbatim_doc_comment)]
+    fit: Vec<String>,
+
     /// Frame Per Second
     #[arg(short = 'r', long = "fps", default_value_t = 30.0)]
     fps: f32,
@@ -79,16 +167,65 @@ struct Cli {
     ///     "3", "ShortestFirst", "shortest"
     ///     "4", "LongestFirst", "longest"
     ///     "5", "RandomWithLargestLast", "rwll"
+    ///     "5s", "RandomWithLargestLastSeeded", "rwlls"
     #[arg(long = "ord", verbatim_doc_comment)]
     ord: Option<String>,
 
-    /// select seed for ord when RandomSeeded is chosen
+    /// select seed for ord when RandomSeeded or RandomWithLargestLastSeeded is chosen
     #[arg(long = "ord-opt", default_value_t = 1337)]
     ord_opt: u64,
 
+    /// What to do once a group runs out of videos:
+    ///     "stop" end the output with the shortest group (default)
+    ///     "loop" replay the group from its first video
+    ///     "hold" freeze the last frame of the group
+    ///     "black" or "black:<hex colour>" fill the tile with a solid colour, eg: black:#202020
+    ///     "reflow" switch to a shape with one fewer tile
+    #[arg(long = "on-exhaust", default_value = "stop", verbatim_doc_comment)]
+    on_exhaust: String,
+
+    /// Target output length as hh:mm:ss, each group keeps the videos that come closest to it
+    /// and the output is cut to exactly this length
+    /// - Example: --duration 30:00
+    #[arg(long = "duration", verbatim_doc_comment)]
+    duration: Option<String>,
+
     /// removes audio completely
     #[arg(long = "no-audio", action)]
     audio: bool,
+    /// export and mix the audio after the video has been encoded, slower but
+    /// does not need the encoder to read audio while encoding
+    #[arg(long = "two-pass-audio", action, conflicts_with = "audio", verbatim_doc_comment)]
+    two_pass_audio: bool,
+    /// Which tiles are heard:
+    ///     "all" every tile at the shape's pan and gain (default)
+    ///     "hero" only the largest tile
+    ///     "loudest" follow the loudest tile, the others are turned down
+    ///     "rotate:<time>" move to the next tile every <time>, eg: rotate:60s
+    #[arg(long = "audio-focus", default_value = "all", verbatim_doc_comment, conflicts_with_all = ["audio", "two_pass_audio"])]
+    audio_focus: String,
+    /// Audio streams in the output:
+    ///     "mix" the mixed stereo only (default)
+    ///     "stems" one stream per group, titled "Tile <n>"
+    ///     "both" the mix as the default stream followed by one stream per group, best written to .mkv
+    #[arg(long = "audio-tracks", default_value = "mix", verbatim_doc_comment, conflicts_with = "audio")]
+    audio_tracks: String,
+    /// silence a group in the mix, groups count from 1, can be repeated
+    /// - Example: --mute-group 3
+    #[arg(long = "mute-group", action=clap::ArgAction::Append, verbatim_doc_comment, conflicts_with = "audio")]
+    mute_group: Vec<String>,
+    /// change a group's volume as <group>=<dB>, can be repeated
+    /// - Example: --group-gain 2=-6dB
+    #[arg(long = "group-gain", action=clap::ArgAction::Append, verbatim_doc_comment, conflicts_with = "audio", allow_hyphen_values = true)]
+    group_gain: Vec<String>,
+    /// place a group from -1.0 (left) to 1.0 (right) as <group>=<pan>, can be repeated
+    /// - Example: --group-pan 1=-0.8
+    #[arg(long = "group-pan", action=clap::ArgAction::Append, verbatim_doc_comment, conflicts_with = "audio", allow_hyphen_values = true)]
+    group_pan: Vec<String>,
+
+    /// also write a CMX3600 edl of each tile next to the output, as <output>.tile<n>.edl
+    #[arg(long = "edl", action)]
+    edl: bool,
 
     /// set hardware encoder to AMD d3d11va
     #[arg(long = "encode-amd", visible_alias = "amd", action, conflicts_with = "hardware_nvidea")]
@@ -106,6 +243,44 @@ struct Cli {
     #[arg(long = "av1", action)]
     encode_av1: bool,
 
+    /// when splitting a single folder, do not route portrait clips to tall tiles
+    /// and landscape clips to wide tiles
+    #[arg(long = "no-orient", action, verbatim_doc_comment)]
+    no_orient: bool,
+
+    /// write a single composite frame at this time of the output to a png then exit,
+    /// only the clips on screen at that time are decoded
+    /// - Example: --preview 00:12:30 --preview-out frame.png
+    #[arg(long = "preview", verbatim_doc_comment)]
+    preview: Option<String>,
+    /// png file written by --preview
+    #[arg(long = "preview-out", default_value = "preview.png", requires = "preview")]
+    preview_out: PathBuf,
+
+    /// join this many synthetic frames with every shape at the output size, print the frames per second then exit
+    /// - Example: --bench-joiner 300 -x 3840 -y 2160
+    #[arg(long = "bench-joiner", verbatim_doc_comment)]
+    bench_joiner: Option<u32>,
+
+    /// load a job saved with --save-project, anything also given on the command line replaces the saved value
+    /// - Example: --project job.toml --ord-opt 7
+    #[arg(long = "project", verbatim_doc_comment)]
+    project: Option<PathBuf>,
+    /// save this job, with settings.ini and anything typed in as they were used, to a project file
+    /// - Example: --save-project job.toml
+    #[arg(long = "save-project", verbatim_doc_comment)]
+    save_project: Option<PathBuf>,
+
+    /// probe every clip again instead of using the probe cache in the user's cache folder
+    #[arg(long = "no-cache", action)]
+    no_cache: bool,
+    /// encoder arguments from --project, used in place of settings.ini
+    #[arg(skip)]
+    encoder_settings: Option<Vec<String>>,
+    /// `[Audio]` settings from --project, used in place of settings.ini
+    #[arg(skip)]
+    audio_settings: Option<Vec<(String, String)>>,
+
     /// print length of resulting video then exit
     #[arg(short = 'l', long = "length", action)]
     print_length: bool,
@@ -113,9 +288,35 @@ struct Cli {
     #[arg(short = 'L', long = "LENGTH", action)]
     print_length2: bool,
 
+    /// plan a job then edit and render it, without a command the job is planned and rendered in one go
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn try_check_local_settings() -> Option<Vec<String>> {
+/// a job split into planning and rendering, switches are given before the command
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// scan, group and sort the clips, then write the clips of each tile in play order to a json file
+    /// and exit without encoding. the file can be edited before it is rendered
+    /// - Example: video_joiner -f D:\clips -s Quad --ord seed plan plan.json
+    #[command(verbatim_doc_comment)]
+    Plan {
+        /// json file to write
+        file: PathBuf,
+    },
+    /// render the clips of a plan exactly as listed, in place of scanning folders,
+    /// the shape is taken from the plan
+    /// - Example: video_joiner -o joined.mp4 render --plan plan.json
+    #[command(verbatim_doc_comment)]
+    Render {
+        /// json file written by plan
+        #[arg(long = "plan")]
+        plan: PathBuf,
+    },
+}
+
+/// settings.ini in the working directory, if there is one
+fn local_settings() -> Option<Ini> {
     let current_env = match std::env::current_dir() {
         Ok(e) => { e }
         Err(_) => {
@@ -127,11 +328,11 @@ fn try_check_local_settings() -> Option<Vec<String>> {
 
     println!("INI FILE {:?}", current_ini);
 
-    let ini_file = Ini::load_from_file(current_ini);
-    let ini_file = match ini_file {
-        Ok(e) => { e }
-        Err(_) => { return None }
-    };
+    Ini::load_from_file(current_ini).ok()
+}
+
+fn try_check_local_settings() -> Option<Vec<String>> {
+    let ini_file = local_settings()?;
 
     let mut keys = vec![];
 
@@ -151,6 +352,43 @@ fn try_check_local_settings() -> Option<Vec<String>> {
     Some(keys)
 }
 
+/// the `[Audio]` section of settings.ini
+fn local_audio_settings() -> Vec<(String, String)> {
+    let ini_file = local_settings();
+    ini_file.iter()
+        .filter_map(|ini| ini.section("Audio".into()))
+        .flat_map(|s| s.iter())
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// the `[Audio]` settings, then `--mute-group`, `--group-gain` and `--group-pan` on top
+fn audio_overrides(settings: &[(String, String)], mute_group: &[String], group_gain: &[String],
+                   group_pan: &[String]) -> AudioOverrides {
+    let mut overrides = AudioOverrides::default();
+    for (k, v) in settings {
+        let set = match k.as_str() {
+            "mute_group" => v.split(',').try_for_each(|g| overrides.mute(g)),
+            "group_gain" => v.split(',').try_for_each(|g| overrides.gain(g)),
+            "group_pan" => v.split(',').try_for_each(|g| overrides.pan(g)),
+            _ => match k.strip_prefix("chain_") {
+                Some(group) => overrides.chain(group, v),
+                None => Err("unknown key".to_string()),
+            },
+        };
+        set.unwrap_or_else(|e| panic!("settings.ini [Audio] {} = {}: {}", k, v, e));
+    }
+    for g in mute_group {
+        overrides.mute(g).unwrap_or_else(|e| panic!("--mute-group {}", e));
+    }
+    for g in group_gain {
+        overrides.gain(g).unwrap_or_else(|e| panic!("--group-gain {}", e));
+    }
+    for g in group_pan {
+        overrides.pan(g).unwrap_or_else(|e| panic!("--group-pan {}", e));
+    }
+    overrides
+}
 
 fn set_encoder_args(hardware_amd: bool, hardware_nvidea: bool, encode_av1: bool,
                     encode_hvec: bool, encode_h264: bool) -> Vec<String> {
@@ -201,7 +439,7 @@ fn request_input(message: &str) -> String {
 
 fn get_folders_multi(shape: FrameShape) -> Vec<MultiPathBuf> {
     let switch: bool = match shape {
-        FrameShape::Dual | FrameShape::Triple | FrameShape::Quad => {
+        FrameShape::Dual | FrameShape::Triple | FrameShape::Quad | FrameShape::Grid { .. } => {
             let req = request_input(
                 "use automatic video list from a single folder?"
             ).to_lowercase();
@@ -217,9 +455,76 @@ fn get_folders_multi(shape: FrameShape) -> Vec<MultiPathBuf> {
     items
 }
 
-fn run_from_cli(args: Cli) -> (VideoGroup, bool, Vec<String>, (bool, bool)) {
-    let split_format = FrameShape::from_str_opt(args.split_format);
-    let sort_ord = match args.ord.unwrap_or("Random".to_string()).to_lowercase().as_str().trim() {
+/// split `--fit` values into the default mode and the per group modes, groups are numbered from 1
+fn parse_fit_args(fit_args: &[String]) -> (FitMode, HashMap<usize, FitMode>) {
+    let mut fit = FitMode::Crop;
+    let mut fit_groups = HashMap::new();
+    for arg in fit_args {
+        let parsed = match arg.split_once('=') {
+            None => FitMode::from_str(arg).map(|f| fit = f),
+            Some((grp, mode)) => match grp.trim().parse::<usize>() {
+                Ok(grp) if grp > 0 => FitMode::from_str(mode).map(|f| { fit_groups.insert(grp - 1, f); }),
+                _ => Err(format!("'{}' is not a valid group number, groups start at 1", grp)),
+            },
+        };
+        if let Err(e) = parsed {
+            panic!("--fit {}: {}", arg, e);
+        }
+    }
+    (fit, fit_groups)
+}
+
+/// collect `--group N=PATH` values into one input per group, in group order
+fn parse_group_args(group_args: &[String]) -> Vec<MultiPathBuf> {
+    let mut groups: Vec<Option<MultiPathBuf>> = vec![];
+    for arg in group_args {
+        let (grp, path) = match arg.split_once('=') {
+            Some((grp, path)) => (grp.trim().parse::<usize>().unwrap_or(0), path),
+            None => (0, arg.as_str()),
+        };
+        if grp == 0 {
+            panic!("--group {}: expected '<group number>=<path>', groups start at 1", arg);
+        }
+        let path = MultiPathBuf::from_str(path).unwrap_or_else(|e| panic!("--group {}: {}", arg, e));
+        if groups.len() < grp {
+            groups.resize(grp, None);
+        }
+        match &mut groups[grp - 1] {
+            Some(g) => g.extend(path),
+            g => *g = Some(path),
+        }
+    }
+    groups.into_iter()
+        .enumerate()
+        .map(|(i, g)| g.unwrap_or_else(|| panic!("--group {} is missing, groups must be numbered without gaps", i + 1)))
+        .collect()
+}
+
+/// split `--role name=PATH` values into role names and inputs
+fn parse_role_args(role_args: &[String]) -> Vec<(String, MultiPathBuf)> {
+    role_args.iter().map(|arg| {
+        let (role, path) = arg.split_once('=')
+            .unwrap_or_else(|| panic!("--role {}: expected '<role>=<path>'", arg));
+        let path = MultiPathBuf::from_str(path).unwrap_or_else(|e| panic!("--role {}: {}", arg, e));
+        (role.trim().to_lowercase(), path)
+    }).collect()
+}
+
+/// `--preview` time in seconds and the png to write
+type Preview = Option<(f64, PathBuf)>;
+
+fn run_from_cli(mut args: Cli, plan: Option<Plan>) -> (VideoGroup, AudioMode, Vec<String>, (bool, bool), Preview) {
+    let split_format = match &args.layout_file {
+        Some(path) => FrameShape::from_layout_file(path),
+        None => FrameShape::from_str_opt(args.split_format.clone()),
+    };
+    let plan_tiles = plan.map(|p| p.tiles(split_format.count() as usize)
+        .unwrap_or_else(|e| panic!("render --plan {}", e)));
+    let on_exhaust = OnExhaust::from_str(&args.on_exhaust).unwrap_or_else(|e| panic!("--on-exhaust {}", e));
+    let audio_focus = AudioFocus::from_str(&args.audio_focus).unwrap_or_else(|e| panic!("--audio-focus {}", e));
+    let audio_tracks = AudioTracks::from_str(&args.audio_tracks).unwrap_or_else(|e| panic!("--audio-tracks {}", e));
+    let duration = args.duration.as_ref().map(|d| hhmmss_to_seconds(d).unwrap_or_else(|e| panic!("--duration {}", e)));
+    let sort_ord = match args.ord.clone().unwrap_or("Random".to_string()).to_lowercase().as_str().trim() {
         "0" | "as_input" | "none" => SortOrder::Nan,
         "1" | "random" | "rand" => SortOrder::Random,
         "2" | "randomseeded" | "seed" => SortOrder::RandomSeeded(args.ord_opt),
@@ -227,16 +532,50 @@ fn run_from_cli(args: Cli) -> (VideoGroup, bool, Vec<String>, (bool, bool)) {
         "3" | "shortestfirst" | "shortest" => SortOrder::ShortestFirst,
         "4" | "longestfirst" | "longest" => SortOrder::LongestFirst,
         "5" | "randomwithlargestlast" | "rwll" => SortOrder::RandomWithLargestLast,
+        "5s" | "randomwithlargestlastseeded" | "rwlls" => SortOrder::RandomWithLargestLastSeeded(args.ord_opt),
         _ => { panic!("That is not a valid SortOrder") }
     };
+    // a saved project has to shuffle the same way each time it is loaded, so pick a seed for it
+    let sort_ord = match sort_ord {
+        SortOrder::Random if args.save_project.is_some() => {
+            args.ord = Some("seed".to_string());
+            args.ord_opt = rand::random();
+            println!("Random order saved with seed {}", args.ord_opt);
+            SortOrder::RandomSeeded(args.ord_opt)
+        }
+        SortOrder::RandomWithLargestLast if args.save_project.is_some() => {
+            args.ord = Some("rwlls".to_string());
+            args.ord_opt = rand::random();
+            println!("Random order saved with seed {}", args.ord_opt);
+            SortOrder::RandomWithLargestLastSeeded(args.ord_opt)
+        }
+        sort_ord => sort_ord,
+    };
 
 
-    let mut folder_target = args.input_folder;
-    if folder_target.len() == 0 {
+    let scan_filter = ScanFilter::new(args.recursive, &args.include, &args.exclude)
+        .unwrap_or_else(|e| panic!("--include/--exclude {}", e));
+    let roles: Vec<_> = parse_role_args(&args.role).into_iter()
+        .map(|(role, src)| (role, src.with_filter(scan_filter.clone())))
+        .collect();
+    let mut folder_target = match args.group.is_empty() {
+        true => args.input_folder.clone(),
+        false => parse_group_args(&args.group),
+    };
+    if folder_target.is_empty() && roles.is_empty() && plan_tiles.is_none() {
         folder_target = get_folders_multi(split_format.clone());
     }
+    let folder_target: Vec<MultiPathBuf> = folder_target.into_iter()
+        .map(|src| src.with_filter(scan_filter.clone()))
+        .collect();
+
+    let preview = args.preview.as_ref().map(|t| {
+        let time = hhmmss_to_seconds(t).unwrap_or_else(|e| panic!("--preview {}", e));
+        (time, args.preview_out.clone())
+    });
 
-    let output_file = args.output_file.unwrap_or_else(|| {
+    // the output file is not needed when only rendering a preview
+    let output_file = args.output_file.clone().or(preview.as_ref().map(|(_, p)| p.clone())).unwrap_or_else(|| {
         PathBuf::from(request_input("Output File Name: ").as_str().trim())
     });
 
@@ -249,19 +588,64 @@ fn run_from_cli(args: Cli) -> (VideoGroup, bool, Vec<String>, (bool, bool)) {
     let print_time_only: (bool, bool) = (args.print_length, args.print_length2);
 
     vid_edit_data.set_fps(args.fps);
+    let (fit, fit_groups) = parse_fit_args(&args.fit);
+    vid_edit_data.set_fit(fit, fit_groups);
+    // tile positions are needed before grouping to route clips by orientation
+    vid_edit_data.set_shape(split_format.clone());
+
+    let encoder_args = args.encoder_settings.clone().unwrap_or_else(|| set_encoder_args(
+        args.hardware_amd,
+        args.hardware_nvidea,
+        args.encode_av1,
+        args.encode_hvec,
+        args.encode_h264,
+    ));
+    let audio = match (args.audio, args.two_pass_audio) {
+        (true, _) => AudioMode::Off,
+        (false, true) => AudioMode::TwoPass,
+        (false, false) => AudioMode::Live,
+    };
+    let audio_settings = match audio {
+        AudioMode::Off => vec![],
+        _ => args.audio_settings.clone().unwrap_or_else(local_audio_settings),
+    };
 
+    if let Some(path) = &args.save_project {
+        let project = Project::from_cli(&args, Resolved {
+            output: &output_file,
+            shape: split_format.name(),
+            folders: &folder_target,
+            width: vid_edit_data.output_width,
+            height: vid_edit_data.output_height,
+            encoder: &encoder_args,
+            audio: &audio,
+            audio_settings: &audio_settings,
+        });
+        project.save(path).unwrap_or_else(|e| panic!("--save-project {}", e));
+        println!("Project saved to {:?}", path);
+    }
 
-    let mut vid = match folder_target.len() {
-        0 => { panic!("No folders found") }
-        1 => {
+    let mut vid = match (plan_tiles, folder_target.len()) {
+        (Some(tiles), _) => VideoGroup::new_from_plan(tiles, output_file, split_format.clone()),
+        (None, 0) if !roles.is_empty() => {
+            VideoGroup::new_from_bindings(
+                split_format.bind_roles(roles).unwrap_or_else(|e| panic!("{}", e)),
+                output_file,
+                split_format.clone(),
+                sort_ord,
+            )
+        }
+        (None, 0) => { panic!("No folders found") }
+        (None, 1) => {
             VideoGroup::new_from_folder(
                 folder_target.first().unwrap(),
                 output_file,
                 split_format.clone(),
                 sort_ord,
+                args.no_orient.not().then_some(vid_edit_data.rects.as_slice()),
             )
         }
-        2..=15 => {
+        (None, _) => {
             VideoGroup::new_from_folders(
                 folder_target,
                 output_file,
@@ -269,17 +653,30 @@ fn run_from_cli(args: Cli) -> (VideoGroup, bool, Vec<String>, (bool, bool)) {
                 sort_ord,
             )
         }
-        _ => { panic!("More than 5 folders is currently unsupported") }
     };
-    let encoder_args = set_encoder_args(
-        args.hardware_amd,
-        args.hardware_nvidea,
-        args.encode_av1,
-        args.encode_hvec,
-        args.encode_h264,
-    );
-    vid_edit_data.set_shape(split_format.clone());
     vid.set_video_sizer(vid_edit_data);
+    vid.set_on_exhaust(on_exhaust);
+    vid.set_audio_focus(audio_focus);
+    vid.set_audio_tracks(audio_tracks);
+    vid.set_export_edl(args.edl);
+    if !args.audio {
+        vid.set_audio_overrides(audio_overrides(&audio_settings, &args.mute_group, &args.group_gain, &args.group_pan));
+    }
+    match duration {
+        // a plan is used as it is, the output is only cut
+        Some(duration) if matches!(args.command, Some(Command::Render { .. })) => vid.limit_duration(duration),
+        Some(duration) => vid.set_duration(duration),
+        None => {}
+    }
+    // every clip has been probed by now
+    probe::save_cache();
+
+    if let Some(Command::Plan { file }) = &args.command {
+        let plan = Plan::new(split_format.name(), args.layout_file.clone(), vid.plan_tiles());
+        plan.save(file).unwrap_or_else(|e| panic!("plan {}", e));
+        println!("Plan written to {:?}", file);
+        exit(0)
+    }
 
-    (vid, args.audio, encoder_args, print_time_only)
+    (vid, audio, encoder_args, print_time_only, preview)
 }
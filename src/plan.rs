@@ -0,0 +1,176 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::playlist::{Entry, Trim};
+
+/// version written by the `plan` command
+const PLAN_VERSION: u32 = 1;
+
+/// the clips of every tile in play order, written by `plan` and rendered as it is by `render --plan`
+///
+/// ```json
+/// {"version": 1, "shape": "Dual", "tiles": [
+///   {"tile": 1, "clips": ["D:/clips/b.mp4", "D:/clips/a.mp4"]},
+///   {"tile": 2, "clips": [{"src": "D:/clips/c.mp4", "in": 12.5, "out": 40.0}]}
+/// ]}
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Plan {
+    version: u32,
+    /// `--shape` name, None when a layout file is used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) shape: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) layout_file: Option<PathBuf>,
+    tiles: Vec<PlanTile>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct PlanTile {
+    /// counted from 1, as in `--group`
+    tile: usize,
+    clips: Vec<PlanClip>,
+}
+
+/// a clip played in full is written as just its path
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum PlanClip {
+    Full(PathBuf),
+    Trimmed {
+        src: PathBuf,
+        /// seconds into the clip
+        #[serde(rename = "in", default)]
+        start: f64,
+        #[serde(rename = "out", default, skip_serializing_if = "Option::is_none")]
+        end: Option<f64>,
+    },
+}
+
+impl PlanClip {
+    fn src(&self) -> &Path {
+        match self {
+            PlanClip::Full(src) | PlanClip::Trimmed { src, .. } => src,
+        }
+    }
+}
+
+impl From<Entry> for PlanClip {
+    fn from((src, trim): Entry) -> PlanClip {
+        match trim.is_full() {
+            true => PlanClip::Full(src),
+            false => PlanClip::Trimmed { src, start: trim.start, end: trim.end },
+        }
+    }
+}
+
+impl From<PlanClip> for Entry {
+    fn from(clip: PlanClip) -> Entry {
+        match clip {
+            PlanClip::Full(src) => (src, Trim::default()),
+            PlanClip::Trimmed { src, start, end } => (src, Trim { start, end }),
+        }
+    }
+}
+
+impl Plan {
+    pub(crate) fn new(shape: Option<String>, layout_file: Option<PathBuf>, tiles: Vec<Vec<Entry>>) -> Plan {
+        Plan {
+            version: PLAN_VERSION,
+            shape,
+            layout_file,
+            tiles: tiles.into_iter()
+                .enumerate()
+                .map(|(i, clips)| PlanTile { tile: i + 1, clips: clips.into_iter().map(PlanClip::from).collect() })
+                .collect(),
+        }
+    }
+
+    pub(crate) fn load(path: &Path) -> Result<Plan, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read plan {:?}: {}", path, e))?;
+        Self::from_text(&text).map_err(|e| format!("invalid plan {:?}: {}", path, e))
+    }
+
+    fn from_text(text: &str) -> Result<Plan, String> {
+        let plan: Plan = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if plan.version > PLAN_VERSION {
+            return Err(format!("version {} is newer than this program supports ({})", plan.version, PLAN_VERSION));
+        }
+        if plan.shape.is_none() && plan.layout_file.is_none() {
+            return Err("the plan needs a shape or a layout_file".to_string());
+        }
+        Ok(plan)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("failed to write plan {:?}: {}", path, e))
+    }
+
+    /// the clips of each tile in tile order, checked against a shape with `count` tiles
+    pub(crate) fn tiles(self, count: usize) -> Result<Vec<Vec<Entry>>, String> {
+        let mut tiles: Vec<Option<Vec<PlanClip>>> = (0..count).map(|_| None).collect();
+        for t in self.tiles {
+            match tiles.get_mut(t.tile.wrapping_sub(1)) {
+                Some(slot @ None) if !t.clips.is_empty() => *slot = Some(t.clips),
+                Some(None) => return Err(format!("tile {} has no clips", t.tile)),
+                Some(Some(_)) => return Err(format!("tile {} is listed twice", t.tile)),
+                None => return Err(format!("tile {} is not part of a {} tile shape", t.tile, count)),
+            }
+        }
+        let missing: Vec<String> = tiles.iter()
+            .flatten()
+            .flatten()
+            .map(PlanClip::src)
+            .filter(|c| !c.is_file())
+            .map(|c| c.to_string_lossy().to_string())
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("clips not found: {}", missing.join(", ")));
+        }
+        tiles.into_iter()
+            .enumerate()
+            .map(|(i, t)| match t {
+                Some(clips) => Ok(clips.into_iter().map(Entry::from).collect()),
+                None => Err(format!("tile {} is missing", i + 1)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_tiles_checked() {
+        let dir = std::env::temp_dir().join(format!("video_joiner_plan_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let clip = |n: &str| {
+            std::fs::write(dir.join(n), b"").unwrap();
+            (dir.join(n), Trim::default())
+        };
+        let (a, b, c) = (clip("a.mp4"), clip("b.mp4"), clip("c.mp4"));
+        let trimmed = (c.0.clone(), Trim { start: 12.5, end: Some(40.0) });
+        let dual = |tiles| Plan::new(Some("Dual".to_string()), None, tiles);
+
+        let plan = dual(vec![vec![b.clone(), a.clone()], vec![trimmed.clone()]]);
+        let text = serde_json::to_string(&plan).unwrap();
+        assert!(text.contains(r#""tile":2"#) && !text.contains("layout_file"));
+        assert!(text.contains(r#""in":12.5,"out":40.0"#));
+        let loaded = Plan::from_text(&text).unwrap();
+        assert_eq!(loaded, plan);
+        assert_eq!(loaded.tiles(2), Ok(vec![vec![b.clone(), a.clone()], vec![trimmed]]));
+
+        // tiles can be listed in any order, but every tile needs clips that exist
+        let swapped = Plan { tiles: vec![PlanTile { tile: 2, clips: vec![a.clone().into()] }, PlanTile { tile: 1, clips: vec![c.clone().into()] }], ..dual(vec![]) };
+        assert_eq!(swapped.tiles(2), Ok(vec![vec![c.clone()], vec![a.clone()]]));
+        assert!(dual(vec![vec![a.clone()]]).tiles(2).is_err());
+        assert!(dual(vec![vec![a.clone()], vec![(dir.join("gone.mp4"), Trim::default())]]).tiles(2).is_err());
+        assert!(dual(vec![vec![a], vec![b], vec![c]]).tiles(2).is_err());
+        assert!(Plan::from_text(r#"{"version": 1, "tiles": []}"#).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+
+use crate::helper_functions::hhmmss_to_seconds;
+
+/// extensions read as a list of clips rather than a video, compared ignoring case
+const PLAYLIST_EXTENSIONS: [&str; 4] = ["m3u", "m3u8", "txt", "csv"];
+
+/// the part of a clip that is played, in seconds into the source
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Trim {
+    pub(crate) start: f64,
+    /// None plays to the end of the clip
+    pub(crate) end: Option<f64>,
+}
+
+impl Trim {
+    pub(crate) fn is_full(&self) -> bool {
+        self.start == 0.0 && self.end.is_none()
+    }
+
+    /// milliseconds played of a clip `full` milliseconds long
+    pub(crate) fn length_millis(&self, full: i64) -> i64 {
+        let end = self.end.map_or(full, |e| ((e * 1000.0).round() as i64).min(full));
+        (end - (self.start * 1000.0).round() as i64).max(0)
+    }
+}
+
+/// a single clip of a playlist, relative paths are already resolved against the playlist's folder
+pub(crate) type Entry = (PathBuf, Trim);
+
+pub(crate) fn is_playlist(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| PLAYLIST_EXTENSIONS.contains(&e.as_str()))
+}
+
+/// the clips of a playlist in the order listed
+pub(crate) fn read(path: &Path) -> Result<Vec<Entry>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read playlist {:?}: {}", path, e))?;
+    let ext = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    let base = path.parent().unwrap_or(Path::new(""));
+    let entries = match ext.as_str() {
+        "m3u" | "m3u8" => parse_m3u(&text, base),
+        "csv" => parse_fields(&text, base, ','),
+        _ => parse_fields(&text, base, '\t'),
+    };
+    entries.map_err(|e| format!("invalid playlist {:?}: {}", path, e))
+}
+
+/// one path per line, `#EXTVLCOPT:start-time=` and `stop-time=` trim the next path
+fn parse_m3u(text: &str, base: &Path) -> Result<Vec<Entry>, String> {
+    let mut out = vec![];
+    let mut trim = Trim::default();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        if let Some(opt) = line.strip_prefix("#EXTVLCOPT:") {
+            match opt.split_once('=') {
+                Some(("start-time", t)) => trim.start = time(t, n)?,
+                Some(("stop-time", t)) => trim.end = Some(time(t, n)?),
+                _ => {}
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        out.push(entry(base, line, trim, n)?);
+        trim = Trim::default();
+    }
+    Ok(out)
+}
+
+/// one clip per line as `path[,in[,out]]`, split on `sep`. csv fields can be quoted and a
+/// `path` or `file` header is skipped, lines starting with `#` are comments
+fn parse_fields(text: &str, base: &Path, sep: char) -> Result<Vec<Entry>, String> {
+    let mut out = vec![];
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim_start_matches('\u{feff}');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = match sep {
+            ',' => csv_fields(line),
+            _ => line.split(sep).map(str::to_string).collect(),
+        };
+        let field = |i: usize| fields.get(i).map(|f| f.trim()).filter(|f| !f.is_empty());
+        let path = field(0).unwrap_or_default();
+        if n == 0 && ["path", "file"].contains(&path.to_lowercase().as_str()) {
+            continue;
+        }
+        let trim = Trim {
+            start: field(1).map(|t| time(t, n)).transpose()?.unwrap_or(0.0),
+            end: field(2).map(|t| time(t, n)).transpose()?,
+        };
+        out.push(entry(base, path, trim, n)?);
+    }
+    Ok(out)
+}
+
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(String::new()),
+            (c, _) => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+fn time(t: &str, line: usize) -> Result<f64, String> {
+    hhmmss_to_seconds(t).map_err(|e| format!("line {}: {}", line + 1, e))
+}
+
+fn entry(base: &Path, path: &str, trim: Trim, line: usize) -> Result<Entry, String> {
+    if trim.end.is_some_and(|e| e <= trim.start) {
+        return Err(format!("line {}: the out point of {} is not after its in point", line + 1, path));
+    }
+    Ok((base.join(path), trim))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playlists_parsed() {
+        let base = Path::new("/exports");
+        let trim = |start, end| Trim { start, end };
+
+        let m3u = "#EXTM3U\n#EXTINF:12,Intro\nintro.mp4\n#EXTVLCOPT:start-time=5\n#EXTVLCOPT:stop-time=00:01:10\n/clips/b.mp4\n\nc.mp4\n";
+        assert_eq!(parse_m3u(m3u, base).unwrap(), vec![
+            (PathBuf::from("/exports/intro.mp4"), Trim::default()),
+            (PathBuf::from("/clips/b.mp4"), trim(5.0, Some(70.0))),
+            (PathBuf::from("/exports/c.mp4"), Trim::default()),
+        ]);
+
+        let csv = "path,in,out\n\"shots/a, take 2.mp4\",1.5,\nb.mp4,,00:00:30\n# skipped\nc.mp4\n";
+        assert_eq!(parse_fields(csv, base, ',').unwrap(), vec![
+            (PathBuf::from("/exports/shots/a, take 2.mp4"), trim(1.5, None)),
+            (PathBuf::from("/exports/b.mp4"), trim(0.0, Some(30.0))),
+            (PathBuf::from("/exports/c.mp4"), Trim::default()),
+        ]);
+
+        let txt = "a clip.mp4\t10\t20\nb.mp4\n";
+        assert_eq!(parse_fields(txt, base, '\t').unwrap()[0], (PathBuf::from("/exports/a clip.mp4"), trim(10.0, Some(20.0))));
+        assert!(parse_fields("a.mp4\t20\t10\n", base, '\t').is_err());
+        assert!(parse_fields("a.mp4\tsoon\n", base, '\t').is_err());
+
+        assert_eq!(trim(10.0, Some(20.0)).length_millis(60_000), 10_000);
+        assert_eq!(trim(10.0, Some(90.0)).length_millis(60_000), 50_000);
+        assert!(is_playlist(Path::new("list.M3U8")) && !is_playlist(Path::new("clip.mp4")));
+    }
+}
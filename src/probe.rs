@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// version written to the cache file, a cache with any other version is started again
+const CACHE_VERSION: u32 = 1;
+
+/// the probe cache, None when it is turned off, see [enable_cache]
+static CACHE: Mutex<Option<ProbeCache>> = Mutex::new(None);
+
+/// what a single ffprobe call tells about a clip
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ProbeInfo {
+    /// length of the whole clip
+    pub(crate) duration_millis: Option<i64>,
+    /// width and height of the first video stream as displayed, after `rotation` is applied
+    pub(crate) display_size: Option<(u32, u32)>,
+    /// degrees, from the rotate tag or display matrix
+    pub(crate) rotation: i64,
+    pub(crate) fps: Option<f32>,
+    pub(crate) has_audio: bool,
+}
+
+impl ProbeInfo {
+    /// from `ffprobe -show_format -show_streams -of json` output
+    fn from_json(json: &str) -> Option<ProbeInfo> {
+        let probe: Value = serde_json::from_str(json).ok()?;
+        let streams = probe.get("streams")?.as_array()?;
+        // cover art is a video stream too, it is not a video
+        let video = streams.iter().find(|s| {
+            s.get("codec_type").and_then(Value::as_str) == Some("video")
+                && s.pointer("/disposition/attached_pic").and_then(Value::as_i64) != Some(1)
+        });
+        let rotation = video.map(rotation).unwrap_or(0);
+        let duration = probe.pointer("/format/duration")
+            .or_else(|| video?.get("duration"))
+            .and_then(Value::as_str)
+            .and_then(|d| d.trim().parse::<f64>().ok());
+        Some(ProbeInfo {
+            duration_millis: duration.map(|d| (d * 1000.0) as i64),
+            display_size: video.and_then(|v| display_size(v, rotation)),
+            rotation,
+            fps: video.and_then(frame_rate),
+            has_audio: streams.iter().any(|s| s.get("codec_type").and_then(Value::as_str) == Some("audio")),
+        })
+    }
+}
+
+impl std::fmt::Display for ProbeInfo {
+    /// eg: `1080x1920 29.97fps rotated 90 audio`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((w, h)) = self.display_size {
+            write!(f, "{}x{}", w, h)?;
+        }
+        if let Some(fps) = self.fps {
+            write!(f, " {:.2}fps", fps)?;
+        }
+        if self.rotation != 0 {
+            write!(f, " rotated {}", self.rotation)?;
+        }
+        write!(f, " {}", if self.has_audio { "audio" } else { "no audio" })
+    }
+}
+
+fn rotation(stream: &Value) -> i64 {
+    let tag_rotation = stream.pointer("/tags/rotate")
+        .and_then(|r| r.as_str())
+        .and_then(|r| r.trim().parse::<i64>().ok());
+    let side_rotation = stream.get("side_data_list")
+        .and_then(|s| s.as_array())
+        .and_then(|s| s.iter().find_map(|d| d.get("rotation")?.as_f64()))
+        .map(|r| r.round() as i64);
+    tag_rotation.or(side_rotation).unwrap_or(0)
+}
+
+/// clips with a 90 or 270 degree rotation have their width and height swapped
+fn display_size(stream: &Value, rotation: i64) -> Option<(u32, u32)> {
+    let w = stream.get("width")?.as_u64()? as u32;
+    let h = stream.get("height")?.as_u64()? as u32;
+    match rotation.rem_euclid(180) {
+        90 => Some((h, w)),
+        _ => Some((w, h)),
+    }
+}
+
+/// `avg_frame_rate` as a number, `r_frame_rate` when it is unknown (`0/0`)
+fn frame_rate(stream: &Value) -> Option<f32> {
+    let rate = |key: &str| {
+        let (n, d) = stream.get(key)?.as_str()?.split_once('/')?;
+        let (n, d) = (n.parse::<f32>().ok()?, d.parse::<f32>().ok()?);
+        (n > 0.0 && d > 0.0).then_some(n / d)
+    };
+    rate("avg_frame_rate").or_else(|| rate("r_frame_rate"))
+}
+
+/// the clip's size and modification time, a cached probe is only used while both are unchanged
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct Stamp {
+    size: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Stamp> {
+        let meta = std::fs::metadata(path).ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Stamp { size: meta.len(), modified_secs: modified.as_secs(), modified_nanos: modified.subsec_nanos() })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    #[serde(flatten)]
+    stamp: Stamp,
+    #[serde(flatten)]
+    info: ProbeInfo,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    clips: HashMap<PathBuf, CacheEntry>,
+}
+
+#[derive(Debug)]
+struct ProbeCache {
+    file: PathBuf,
+    clips: HashMap<PathBuf, CacheEntry>,
+    changed: bool,
+}
+
+impl ProbeCache {
+    fn load(file: PathBuf) -> ProbeCache {
+        let clips = match std::fs::read_to_string(&file) {
+            Err(_) => HashMap::new(),
+            Ok(text) => match serde_json::from_str::<CacheFile>(&text) {
+                Ok(cache) if cache.version == CACHE_VERSION => cache.clips,
+                _ => {
+                    println!("Probe cache {:?} is not readable, it will be written again", file);
+                    HashMap::new()
+                }
+            },
+        };
+        ProbeCache { file, clips, changed: false }
+    }
+
+    fn get(&self, key: &Path, stamp: Stamp) -> Option<ProbeInfo> {
+        self.clips.get(key).filter(|e| e.stamp == stamp).map(|e| e.info.clone())
+    }
+
+    /// add or replace a clip's probe, a stale entry is replaced by the new one
+    fn insert(&mut self, key: PathBuf, stamp: Stamp, info: ProbeInfo) {
+        self.clips.insert(key, CacheEntry { stamp, info });
+        self.changed = true;
+    }
+
+    /// write the cache next to itself then move it in place, so a run stopped while saving keeps the old cache
+    fn save(&mut self) -> Result<(), String> {
+        if !self.changed {
+            return Ok(());
+        }
+        if let Some(dir) = self.file.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let cache = CacheFile { version: CACHE_VERSION, clips: std::mem::take(&mut self.clips) };
+        let text = serde_json::to_string(&cache).map_err(|e| e.to_string());
+        self.clips = cache.clips;
+        let temp = self.file.with_extension("json.tmp");
+        std::fs::write(&temp, text?).map_err(|e| e.to_string())?;
+        std::fs::rename(&temp, &self.file).map_err(|e| e.to_string())?;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+/// `video_joiner/probe.json` in the user's cache folder
+fn cache_file() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+    Some(dir.join("video_joiner").join("probe.json"))
+}
+
+/// keep probes between runs, without it every clip is probed again on each run
+pub(crate) fn enable_cache() {
+    match cache_file() {
+        Some(file) => *CACHE.lock().unwrap() = Some(ProbeCache::load(file)),
+        None => println!("No cache folder found, clips will be probed on every run"),
+    }
+}
+
+/// write any new probes to the cache file, failing to do so only costs the next run time
+pub(crate) fn save_cache() {
+    if let Some(cache) = CACHE.lock().unwrap().as_mut() {
+        if let Err(e) = cache.save() {
+            println!("Probe cache {:?} could not be saved: {}", cache.file, e);
+        }
+    }
+}
+
+/// probe `path` with ffprobe, or read it from the cache while the file is unchanged
+pub(crate) fn probe(path: &Path) -> Result<ProbeInfo, String> {
+    let key = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let stamp = Stamp::of(path);
+    if let (Some(cache), Some(stamp)) = (CACHE.lock().unwrap().as_ref(), stamp) {
+        if let Some(info) = cache.get(&key, stamp) {
+            return Ok(info);
+        }
+    }
+
+    let out = std::process::Command::new("ffprobe")
+        .args(["-v", "error", "-show_format", "-show_streams", "-of", "json"])
+        .arg(path)
+        .output()
+        .map_err(|e| format!("ffprobe could not be started: {}", e))?;
+    if !out.status.success() {
+        return Err(String::from_utf8_lossy(&out.stderr).trim().to_string());
+    }
+    let info = ProbeInfo::from_json(&String::from_utf8_lossy(&out.stdout))
+        .ok_or("ffprobe output could not be read")?;
+
+    if let (Some(cache), Some(stamp)) = (CACHE.lock().unwrap().as_mut(), stamp) {
+        cache.insert(key, stamp, info.clone());
+    }
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe_json(video: &str) -> ProbeInfo {
+        ProbeInfo::from_json(&format!(r#"{{"streams": [{}], "format": {{"duration": "12.5"}}}}"#, video)).unwrap()
+    }
+
+    #[test]
+    fn display_size_applies_rotation() {
+        let plain = probe_json(r#"{"codec_type": "video", "width": 1920, "height": 1080}"#);
+        assert_eq!(plain.display_size, Some((1920, 1080)));
+
+        let tagged = probe_json(r#"{"codec_type": "video", "width": 1920, "height": 1080, "tags": {"rotate": "90"}}"#);
+        assert_eq!(tagged.display_size, Some((1080, 1920)));
+        assert_eq!(tagged.rotation, 90);
+
+        let side_data = probe_json(r#"{"codec_type": "video", "width": 1920, "height": 1080,
+            "side_data_list": [{"side_data_type": "Display Matrix", "rotation": -90}]}"#);
+        assert_eq!(side_data.display_size, Some((1080, 1920)));
+
+        let flipped = probe_json(r#"{"codec_type": "video", "width": 1920, "height": 1080, "tags": {"rotate": "180"}}"#);
+        assert_eq!(flipped.display_size, Some((1920, 1080)));
+
+        // album art is not a video stream
+        let cover = probe_json(r#"{"codec_type": "video", "width": 600, "height": 600, "disposition": {"attached_pic": 1}}"#);
+        assert_eq!(cover.display_size, None);
+        assert_eq!(ProbeInfo::from_json(r#"{"streams": []}"#).unwrap().display_size, None);
+    }
+
+    #[test]
+    fn audio_streams_counted() {
+        let clip = probe_json(r#"{"codec_type": "video", "width": 1280, "height": 720, "avg_frame_rate": "30000/1001"},
+            {"codec_type": "audio"}"#);
+        assert!(clip.has_audio);
+        assert_eq!(clip.duration_millis, Some(12500));
+        assert!((clip.fps.unwrap() - 29.97).abs() < 0.01);
+        // screen recordings and time-lapses have no audio, ffprobe still lists an empty set
+        assert!(!probe_json(r#"{"codec_type": "video", "avg_frame_rate": "0/0", "r_frame_rate": "25/1"}"#).has_audio);
+        assert_eq!(ProbeInfo::from_json(r#"{}"#), None);
+    }
+
+    #[test]
+    fn cache_invalidated_by_stamp() {
+        let dir = std::env::temp_dir().join(format!("video_joiner_probe_{}", std::process::id()));
+        let file = dir.join("probe.json");
+        let stamp = Stamp { size: 10, modified_secs: 100, modified_nanos: 5 };
+        let info = ProbeInfo { duration_millis: Some(1000), fps: Some(25.0), has_audio: true, ..Default::default() };
+
+        let mut cache = ProbeCache::load(file.clone());
+        cache.insert(PathBuf::from("/clips/a.mp4"), stamp, info.clone());
+        cache.save().unwrap();
+
+        let cache = ProbeCache::load(file.clone());
+        assert_eq!(cache.get(Path::new("/clips/a.mp4"), stamp), Some(info));
+        // a file that changed size or was touched is probed again
+        assert_eq!(cache.get(Path::new("/clips/a.mp4"), Stamp { size: 11, ..stamp }), None);
+        assert_eq!(cache.get(Path::new("/clips/a.mp4"), Stamp { modified_secs: 101, ..stamp }), None);
+
+        std::fs::write(&file, "not json").unwrap();
+        assert!(ProbeCache::load(file).clips.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+
+use crate::helper_functions::MultiPathBuf;
+use crate::switches::AudioMode;
+use crate::Cli;
+
+/// version written by `--save-project`, older files are upgraded by [upgrade] when loaded
+const PROJECT_VERSION: i64 = 1;
+
+/// a complete job, saved with `--save-project` and loaded with `--project`
+///
+/// values are kept as they are given on the command line so loading a project is the same as
+/// typing them again, anything read from settings.ini or asked for is saved as it was resolved
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Project {
+    version: i64,
+    output: Option<PathBuf>,
+    shape: Option<String>,
+    layout_file: Option<PathBuf>,
+    /// one entry per group, several sources in a group are separated by `|`
+    folders: Vec<String>,
+    /// `--role` values, used in place of `folders`
+    roles: Vec<String>,
+    recursive: bool,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    fps: Option<f32>,
+    fit: Vec<String>,
+    ord: Option<String>,
+    ord_opt: Option<u64>,
+    on_exhaust: Option<String>,
+    duration: Option<String>,
+    no_orient: bool,
+    edl: bool,
+    /// encoder arguments as used, so a later settings.ini does not change the project
+    encoder: Vec<String>,
+    audio: ProjectAudio,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct ProjectAudio {
+    /// "live", "two-pass" or "off"
+    mode: Option<String>,
+    focus: Option<String>,
+    tracks: Option<String>,
+    mute_group: Vec<String>,
+    group_gain: Vec<String>,
+    group_pan: Vec<String>,
+    /// the `[Audio]` section of settings.ini as used
+    settings: BTreeMap<String, String>,
+}
+
+/// everything resolved while setting up a job, saved alongside the command line values
+pub(crate) struct Resolved<'a> {
+    pub(crate) output: &'a Path,
+    pub(crate) shape: Option<String>,
+    pub(crate) folders: &'a [MultiPathBuf],
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) encoder: &'a [String],
+    pub(crate) audio: &'a AudioMode,
+    pub(crate) audio_settings: &'a [(String, String)],
+}
+
+impl Project {
+    /// paths are saved absolute, so the project can be loaded from any folder
+    pub(crate) fn from_cli(args: &Cli, resolved: Resolved) -> Project {
+        let mode = match resolved.audio {
+            AudioMode::Off => "off",
+            AudioMode::Live => "live",
+            AudioMode::TwoPass => "two-pass",
+        };
+        let mut project = Project {
+            version: PROJECT_VERSION,
+            output: Some(resolved.output.to_path_buf()),
+            shape: resolved.shape,
+            layout_file: args.layout_file.clone(),
+            folders: match args.role.is_empty() {
+                true => resolved.folders.iter().map(|f| f.to_string()).collect(),
+                false => vec![],
+            },
+            roles: args.role.clone(),
+            recursive: args.recursive,
+            include: args.include.clone(),
+            exclude: args.exclude.clone(),
+            width: Some(resolved.width),
+            height: Some(resolved.height),
+            fps: Some(args.fps),
+            fit: args.fit.clone(),
+            ord: args.ord.clone(),
+            ord_opt: Some(args.ord_opt),
+            on_exhaust: Some(args.on_exhaust.clone()),
+            duration: args.duration.clone(),
+            no_orient: args.no_orient,
+            edl: args.edl,
+            encoder: resolved.encoder.to_vec(),
+            audio: ProjectAudio {
+                mode: Some(mode.to_string()),
+                focus: Some(args.audio_focus.clone()),
+                tracks: Some(args.audio_tracks.clone()),
+                mute_group: args.mute_group.clone(),
+                group_gain: args.group_gain.clone(),
+                group_pan: args.group_pan.clone(),
+                settings: resolved.audio_settings.iter().cloned().collect(),
+            },
+        };
+        project.resolve_paths(&std::env::current_dir().unwrap_or_default());
+        project
+    }
+
+    pub(crate) fn load(path: &Path) -> Result<Project, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read project file {:?}: {}", path, e))?;
+        let mut project = Self::from_text(&text).map_err(|e| format!("invalid project file {:?}: {}", path, e))?;
+        // hand written projects can use paths relative to the project file
+        project.resolve_paths(path.parent().unwrap_or(Path::new("")));
+        Ok(project)
+    }
+
+    /// join relative folders, roles, layout file and output to `base`
+    fn resolve_paths(&mut self, base: &Path) {
+        let sources = |s: &str| match MultiPathBuf::from_str(s) {
+            Ok(src) => src.resolved(base).to_string(),
+            // reported when the project is applied
+            Err(_) => s.to_string(),
+        };
+        self.folders = self.folders.iter().map(|f| sources(f)).collect();
+        self.roles = self.roles.iter()
+            .map(|r| match r.split_once('=') {
+                Some((role, src)) => format!("{}={}", role, sources(src)),
+                None => r.clone(),
+            })
+            .collect();
+        self.layout_file = self.layout_file.as_ref().map(|p| base.join(p));
+        self.output = self.output.as_ref().map(|p| base.join(p));
+    }
+
+    fn from_text(text: &str) -> Result<Project, String> {
+        let mut value: toml::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+        let version = match value.get("version") {
+            Some(toml::Value::Integer(v)) => *v,
+            Some(_) => return Err("version must be a whole number".to_string()),
+            None => return Err("missing version".to_string()),
+        };
+        if version > PROJECT_VERSION {
+            return Err(format!("version {} is newer than this program supports ({})", version, PROJECT_VERSION));
+        }
+        upgrade(&mut value, version)?;
+        value.try_into().map_err(|e: toml::de::Error| e.to_string())
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("failed to write project file {:?}: {}", path, e))
+    }
+
+    /// fill `args` from the project, anything given on the command line is kept
+    pub(crate) fn apply(&self, args: &mut Cli, matches: &ArgMatches) {
+        let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        if !given("input_folder") && !given("group") && !given("role") {
+            args.input_folder = self.folders.iter()
+                .map(|f| MultiPathBuf::from_str(f).unwrap_or_else(|e| panic!("project folders {}: {}", f, e)))
+                .collect();
+            args.role = self.roles.clone();
+        }
+        if !given("recursive") {
+            args.recursive = self.recursive;
+        }
+        if !given("include") {
+            args.include = self.include.clone();
+        }
+        if !given("exclude") {
+            args.exclude = self.exclude.clone();
+        }
+        if !given("split_format") && !given("layout_file") {
+            args.split_format = self.shape.clone();
+            args.layout_file = self.layout_file.clone();
+        }
+        if !given("output_file") && self.output.is_some() {
+            args.output_file = self.output.clone();
+        }
+        if !given("output_width") && self.width.is_some() {
+            args.output_width = self.width;
+        }
+        if !given("output_height") && self.height.is_some() {
+            args.output_height = self.height;
+        }
+        if let Some(fps) = self.fps.filter(|_| !given("fps")) {
+            args.fps = fps;
+        }
+        if !given("fit") {
+            args.fit = self.fit.clone();
+        }
+        if !given("ord") && self.ord.is_some() {
+            args.ord = self.ord.clone();
+        }
+        if let Some(ord_opt) = self.ord_opt.filter(|_| !given("ord_opt")) {
+            args.ord_opt = ord_opt;
+        }
+        if let Some(on_exhaust) = self.on_exhaust.as_ref().filter(|_| !given("on_exhaust")) {
+            args.on_exhaust = on_exhaust.clone();
+        }
+        if !given("duration") && self.duration.is_some() {
+            args.duration = self.duration.clone();
+        }
+        if !given("no_orient") {
+            args.no_orient = self.no_orient;
+        }
+        if !given("edl") {
+            args.edl = self.edl;
+        }
+
+        let encoder_flags = ["hardware_amd", "hardware_nvidea", "encode_h264", "encode_hvec", "encode_av1"];
+        if !encoder_flags.iter().any(|f| given(f)) && !self.encoder.is_empty() {
+            args.encoder_settings = Some(self.encoder.clone());
+        }
+
+        let audio = &self.audio;
+        if !given("audio") && !given("two_pass_audio") {
+            match audio.mode.as_deref() {
+                Some("off") => args.audio = true,
+                Some("two-pass") => args.two_pass_audio = true,
+                _ => {}
+            }
+        }
+        if let Some(focus) = audio.focus.as_ref().filter(|_| !given("audio_focus")) {
+            args.audio_focus = focus.clone();
+        }
+        if let Some(tracks) = audio.tracks.as_ref().filter(|_| !given("audio_tracks")) {
+            args.audio_tracks = tracks.clone();
+        }
+        if !given("mute_group") {
+            args.mute_group = audio.mute_group.clone();
+        }
+        if !given("group_gain") {
+            args.group_gain = audio.group_gain.clone();
+        }
+        if !given("group_pan") {
+            args.group_pan = audio.group_pan.clone();
+        }
+        args.audio_settings = Some(audio.settings.iter().map(|(k, v)| (k.clone(), v.clone())).collect());
+    }
+}
+
+/// bring a project written by an older version up to [PROJECT_VERSION], one version at a time
+fn upgrade(_project: &mut toml::Table, version: i64) -> Result<(), String> {
+    match version {
+        PROJECT_VERSION => Ok(()),
+        v => Err(format!("version {} is not a known project version", v)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_round_trip() {
+        let project = Project {
+            version: PROJECT_VERSION,
+            output: Some(PathBuf::from("/out/joined.mkv")),
+            shape: Some("grid:4x3".to_string()),
+            folders: vec!["/clips/left|/mnt/nas/left".to_string(), "/clips/right".to_string()],
+            width: Some(3840),
+            height: Some(2160),
+            ord: Some("seed".to_string()),
+            ord_opt: Some(42),
+            encoder: vec!["-c:v".to_string(), "hevc_amf".to_string()],
+            audio: ProjectAudio {
+                mode: Some("two-pass".to_string()),
+                group_gain: vec!["2=-6dB".to_string()],
+                settings: BTreeMap::from([("chain_2".to_string(), "highpass=f=200".to_string())]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let text = toml::to_string_pretty(&project).unwrap();
+        assert_eq!(Project::from_text(&text), Ok(project));
+
+        // fields added later keep their defaults in older files
+        let old = Project::from_text("version = 1\nshape = \"Quad\"\n").unwrap();
+        assert_eq!(old.shape.as_deref(), Some("Quad"));
+        assert!(old.folders.is_empty());
+
+        // relative paths are found from the project's folder
+        let mut relative = Project {
+            output: Some(PathBuf::from("out/joined.mkv")),
+            layout_file: Some(PathBuf::from("/layouts/wide.toml")),
+            folders: vec!["clips/left|/mnt/nas/left".to_string()],
+            roles: vec!["hero=vertical".to_string()],
+            ..Default::default()
+        };
+        relative.resolve_paths(Path::new("/jobs"));
+        assert_eq!(relative.output, Some(PathBuf::from("/jobs/out/joined.mkv")));
+        assert_eq!(relative.layout_file, Some(PathBuf::from("/layouts/wide.toml")));
+        assert_eq!(relative.folders, vec!["/jobs/clips/left|/mnt/nas/left".to_string()]);
+        assert_eq!(relative.roles, vec!["hero=/jobs/vertical".to_string()]);
+
+        assert!(Project::from_text("shape = \"Quad\"").is_err());
+        assert!(Project::from_text("version = 99").is_err());
+    }
+}
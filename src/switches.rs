@@ -1,8 +1,10 @@
+use crate::helper_functions::hhmmss_to_seconds;
 use crate::video::Video;
 use rand::prelude::SliceRandom;
 use rand::rngs::StdRng;
 use rand::thread_rng;
-use rand::SeedableRng;
+use rand::{Rng, SeedableRng};
+use std::str::FromStr;
 
 #[derive(Clone, Debug)]
 pub(crate) enum SortOrder {
@@ -12,6 +14,7 @@ pub(crate) enum SortOrder {
     ShortestFirst,
     LongestFirst,
     RandomWithLargestLast,
+    RandomWithLargestLastSeeded(u64),
     Nan,
 }
 impl SortOrder {
@@ -19,7 +22,7 @@ impl SortOrder {
         // if SortOrder needs the length we make sure it exists here first
         match self {
             SortOrder::ShortestFirst | SortOrder::LongestFirst |
-            SortOrder::RandomWithLargestLast => {
+            SortOrder::RandomWithLargestLast | SortOrder::RandomWithLargestLastSeeded(_) => {
                 videos.iter_mut().for_each(|f| { let _ = f.get_length(); });
             }
             _ => {}
@@ -53,17 +56,256 @@ impl SortOrder {
                 videos
             }
             SortOrder::RandomWithLargestLast => {
-                let mut rng = thread_rng();
-                videos.shuffle(&mut rng);
-                let longest = videos.iter().max_by_key(|x| x.length_millis.unwrap_or(0)).unwrap();
-                let v_longest = videos.remove(
-                    videos.iter().position(|v| std::ptr::addr_eq(v, longest)).unwrap()
-                );
-                videos.push(v_longest);
-                videos
+                largest_last(videos, &mut thread_rng())
+            }
+            SortOrder::RandomWithLargestLastSeeded(s) => {
+                largest_last(videos, &mut <StdRng as SeedableRng>::seed_from_u64(*s))
+            }
+        }
+    }
+}
+
+/// shuffle then move the longest video to the end
+fn largest_last(mut videos: Vec<Video>, rng: &mut impl Rng) -> Vec<Video> {
+    videos.shuffle(rng);
+    let Some(longest) = videos.iter().max_by_key(|x| x.length_millis.unwrap_or(0)) else {
+        return videos;
+    };
+    let v_longest = videos.remove(
+        videos.iter().position(|v| std::ptr::addr_eq(v, longest)).unwrap()
+    );
+    videos.push(v_longest);
+    videos
+}
+
+/// how a clip is fitted into its tile, the tile size is always kept exactly
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum FitMode {
+    /// scale to fill the tile then crop the overflow (default)
+    Crop,
+    /// scale to fit inside the tile, letterbox with the given ffmpeg colour
+    Pad(String),
+    /// scale to fit inside the tile, fill the bars with a blurred and zoomed copy of the clip
+    Blur,
+}
+
+impl FromStr for FitMode {
+    type Err = String;
+
+    /// accepts `crop`, `pad`, `pad:<colour>` or `blur`, eg: `pad:#202020` or `pad:white`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (mode, colour) = match s.split_once(':') {
+            Some((mode, colour)) => (mode, Some(colour.trim())),
+            None => (s, None),
+        };
+        match (mode.to_lowercase().as_str(), colour) {
+            ("crop", None) => Ok(FitMode::Crop),
+            ("blur", None) => Ok(FitMode::Blur),
+            ("pad", None) => Ok(FitMode::Pad("black".to_string())),
+            ("pad", Some(c)) if !c.is_empty() && c.chars().all(|c| c.is_ascii_alphanumeric() || "#@.".contains(c)) => {
+                Ok(FitMode::Pad(c.to_string()))
             }
+            ("pad", Some(c)) => Err(format!("'{}' is not a valid pad colour, use a name or hex value eg: pad:#202020", c)),
+            _ => Err(format!("'{}' is not a valid fit mode, options are: crop, pad, pad:<colour>, blur", s)),
         }
     }
 }
 
+impl FitMode {
+    /// ffmpeg filter chain from `[0:v]` to `[b]`, output is exactly `w` x `h`
+    ///
+    /// frames are converted to rgb24 before cropping / padding so odd tile sizes are
+    /// not rounded down by chroma subsampling
+    pub(crate) fn filtergraph(&self, w: u32, h: u32) -> String {
+        match self {
+            FitMode::Crop => {
+                format!("[0:v]scale={w}:{h}:force_original_aspect_ratio=increase,format=rgb24[a];\
+                        [a]crop=w={w}:h={h}[b]")
+            }
+            FitMode::Pad(colour) => {
+                format!("[0:v]scale={w}:{h}:force_original_aspect_ratio=decrease,format=rgb24[a];\
+                        [a]pad={w}:{h}:(ow-iw)/2:(oh-ih)/2:color={colour}[b]")
+            }
+            FitMode::Blur => {
+                format!("[0:v]split=2[bg][fg];\
+                        [bg]scale={w}:{h}:force_original_aspect_ratio=increase,format=rgb24,crop={w}:{h},gblur=sigma=20[bgb];\
+                        [fg]scale={w}:{h}:force_original_aspect_ratio=decrease,format=rgb24[a];\
+                        [bgb][a]overlay=(W-w)/2:(H-h)/2:format=rgb[b]")
+            }
+        }
+    }
+}
+
+/// what happens to a tile once its group has run out of videos
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum OnExhaust {
+    /// end the output with the shortest group (default)
+    Stop,
+    /// replay the group from its first video
+    Loop,
+    /// freeze the last frame of the group
+    Hold,
+    /// fill the tile with a solid rgb colour
+    Black([u8; 3]),
+    /// switch to a shape with one fewer tile, the remaining groups are re-sized to fit
+    Reflow,
+}
+
+impl FromStr for OnExhaust {
+    type Err = String;
+
+    /// accepts `stop`, `loop`, `hold`, `black`, `black:<hex colour>` or `reflow`, eg: `black:#202020`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        match s.split_once(':') {
+            None => match s.as_str() {
+                "stop" => Ok(OnExhaust::Stop),
+                "loop" => Ok(OnExhaust::Loop),
+                "hold" => Ok(OnExhaust::Hold),
+                "black" => Ok(OnExhaust::Black([0, 0, 0])),
+                "reflow" => Ok(OnExhaust::Reflow),
+                _ => Err(format!("'{}' is not a valid policy, options are: stop, loop, hold, black, black:<colour>, reflow", s)),
+            },
+            Some(("black", hex)) => {
+                let hex = hex.trim_start_matches('#');
+                let rgb = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6);
+                match rgb {
+                    Some(rgb) => Ok(OnExhaust::Black([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])),
+                    None => Err(format!("'{}' is not a hex colour, eg: black:#202020", hex)),
+                }
+            }
+            Some(_) => Err(format!("'{}' is not a valid policy, only black takes a colour", s)),
+        }
+    }
+}
 
+/// how the output gets its audio
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum AudioMode {
+    /// `--no-audio`
+    Off,
+    /// decoded alongside the video and mixed by the encoder in the same pass (default)
+    Live,
+    /// `--two-pass-audio`, exported per clip once the video is finished then joined to it
+    TwoPass,
+}
+
+/// which tiles are heard, see [crate::focus::Focus]
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum AudioFocus {
+    /// every tile at the shape's pan and gain (default)
+    All,
+    /// only the largest tile
+    Hero,
+    /// follow the tile with the highest short-term loudness, the others are ducked
+    Loudest,
+    /// move the focus to the next tile every given number of seconds, the others are ducked
+    Rotate(f64),
+}
+
+impl FromStr for AudioFocus {
+    type Err = String;
+
+    /// accepts `all`, `hero`, `loudest` or `rotate:<time>`, eg: `rotate:60s` or `rotate:1:30`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        match s.split_once(':') {
+            None => match s.as_str() {
+                "all" => Ok(AudioFocus::All),
+                "hero" => Ok(AudioFocus::Hero),
+                "loudest" => Ok(AudioFocus::Loudest),
+                _ => Err(format!("'{}' is not a valid focus, options are: all, hero, loudest, rotate:<time>", s)),
+            },
+            Some(("rotate", time)) => match hhmmss_to_seconds(time.trim_end_matches('s')) {
+                Ok(secs) if secs > 0.0 => Ok(AudioFocus::Rotate(secs)),
+                Ok(_) => Err("rotate time must be more than 0 seconds".to_string()),
+                Err(e) => Err(e),
+            },
+            Some(_) => Err(format!("'{}' is not a valid focus, only rotate takes a time", s)),
+        }
+    }
+}
+
+/// which audio streams the output gets
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum AudioTracks {
+    /// the mixed stereo only (default)
+    Mix,
+    /// one stream per group, unmixed
+    Stems,
+    /// the mix as the default stream, followed by one stream per group
+    Both,
+}
+
+impl AudioTracks {
+    pub(crate) fn mix(&self) -> bool {
+        *self != AudioTracks::Stems
+    }
+    pub(crate) fn stems(&self) -> bool {
+        *self != AudioTracks::Mix
+    }
+}
+
+impl FromStr for AudioTracks {
+    type Err = String;
+
+    /// accepts `mix`, `stems` or `both`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "mix" => Ok(AudioTracks::Mix),
+            "stems" => Ok(AudioTracks::Stems),
+            "both" => Ok(AudioTracks::Both),
+            s => Err(format!("'{}' is not a valid track option, options are: mix, stems, both", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_largest_last_repeats() {
+        let sort = |seed| {
+            let videos = (0..8).map(|i| {
+                let mut v = Video::from_path(format!("{}.mp4", i));
+                v.length_millis = Some(i * 1000);
+                v
+            }).collect();
+            SortOrder::RandomWithLargestLastSeeded(seed).apply_sort(videos).into_iter().map(|v| v.src).collect::<Vec<_>>()
+        };
+        assert_eq!(sort(7), sort(7));
+        assert_eq!(sort(7).last(), Some(&std::path::PathBuf::from("7.mp4")));
+    }
+
+    #[test]
+    fn fit_mode_parsing() {
+        assert_eq!(FitMode::from_str("Crop"), Ok(FitMode::Crop));
+        assert_eq!(FitMode::from_str("pad"), Ok(FitMode::Pad("black".to_string())));
+        assert_eq!(FitMode::from_str("pad:#20A020"), Ok(FitMode::Pad("#20A020".to_string())));
+        assert_eq!(FitMode::from_str("blur"), Ok(FitMode::Blur));
+        assert!(FitMode::from_str("pad:red[x]").is_err());
+        assert!(FitMode::from_str("stretch").is_err());
+        assert!(FitMode::Blur.filtergraph(853, 480).ends_with("[b]"));
+    }
+
+    #[test]
+    fn audio_focus_parsing() {
+        assert_eq!(AudioFocus::from_str("Hero"), Ok(AudioFocus::Hero));
+        assert_eq!(AudioFocus::from_str("rotate:60s"), Ok(AudioFocus::Rotate(60.0)));
+        assert_eq!(AudioFocus::from_str("rotate:1:30"), Ok(AudioFocus::Rotate(90.0)));
+        assert!(AudioFocus::from_str("rotate:0").is_err());
+        assert!(AudioFocus::from_str("loudest:5").is_err());
+    }
+
+    #[test]
+    fn on_exhaust_parsing() {
+        assert_eq!(OnExhaust::from_str("Loop"), Ok(OnExhaust::Loop));
+        assert_eq!(OnExhaust::from_str("black"), Ok(OnExhaust::Black([0, 0, 0])));
+        assert_eq!(OnExhaust::from_str("black:#20A0ff"), Ok(OnExhaust::Black([0x20, 0xa0, 0xff])));
+        assert!(OnExhaust::from_str("black:#20A0").is_err());
+        assert!(OnExhaust::from_str("hold:#202020").is_err());
+        assert!(OnExhaust::from_str("repeat").is_err());
+    }
+}
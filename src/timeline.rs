@@ -0,0 +1,227 @@
+use std::path::{Path, PathBuf};
+
+use ffmpeg_sidecar::command::FfmpegCommand;
+use serde::Serialize;
+use std::ops::Not;
+
+/// frames of a single clip as placed in the output, frames count from 0
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Span {
+    pub(crate) src: PathBuf,
+    /// seconds into the source of the first frame, from a playlist's in point
+    pub(crate) source_in: f64,
+    pub(crate) start: u64,
+    pub(crate) frames: u64,
+}
+
+/// a piece of a group's audio track, `src` is None for silence between clips
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct AudioSpan {
+    pub(crate) src: Option<PathBuf>,
+    /// seconds into `src` to start from
+    pub(crate) source_in: f64,
+    pub(crate) samples: u64,
+}
+
+/// first sample of frame `frame` (counted from 0), rounded down so consecutive frames never overlap
+pub(crate) fn sample_at(frame: u64, fps: f32, rate: u32) -> u64 {
+    (frame as f64 * rate as f64 / fps as f64).floor() as u64
+}
+
+/// audio track of a group from its clips in output order, each clip covers the samples of its own frames
+/// and gaps before a clip are filled with silence. spans past `total_frames` are cut, so the track
+/// is exactly as long as the video
+pub(crate) fn audio_spans(spans: &[Span], total_frames: u64, fps: f32, rate: u32) -> Vec<AudioSpan> {
+    let mut out = vec![];
+    let mut cursor = 0;
+    for span in spans {
+        let start = sample_at(span.start.min(total_frames), fps, rate);
+        let end = sample_at((span.start + span.frames).min(total_frames), fps, rate);
+        if start > cursor {
+            out.push(AudioSpan { src: None, source_in: 0.0, samples: start - cursor });
+        }
+        if end > start.max(cursor) {
+            out.push(AudioSpan { src: Some(span.src.clone()), source_in: span.source_in, samples: end - start.max(cursor) });
+            cursor = end;
+        }
+    }
+    let end = sample_at(total_frames, fps, rate);
+    if end > cursor {
+        out.push(AudioSpan { src: None, source_in: 0.0, samples: end - cursor });
+    }
+    out
+}
+
+/// difference between the audio written and the video length in milliseconds, positive when the audio is longer
+pub(crate) fn drift_millis(samples: u64, total_frames: u64, fps: f32, rate: u32) -> f64 {
+    (samples as f64 - sample_at(total_frames, fps, rate) as f64) * 1000.0 / rate as f64
+}
+
+/// `spans` cut to the first `total_frames` frames of the output, clips starting after it are dropped
+pub(crate) fn clamp(spans: &[Span], total_frames: u64) -> Vec<Span> {
+    spans.iter()
+        .filter(|s| s.start < total_frames)
+        .map(|s| Span { frames: s.frames.min(total_frames - s.start), ..s.clone() })
+        .collect()
+}
+
+/// `output.timeline.json`, every clip shown in each tile
+#[derive(Serialize)]
+struct TimelineFile {
+    fps: f32,
+    frames: u64,
+    tiles: Vec<TimelineTile>,
+}
+
+#[derive(Serialize)]
+struct TimelineTile {
+    /// counted from 1, as in `--group` and the audio stems
+    tile: usize,
+    clips: Vec<TimelineClip>,
+}
+
+#[derive(Serialize)]
+struct TimelineClip {
+    src: PathBuf,
+    /// first frame in the output
+    in_frame: u64,
+    /// frame after the last frame in the output
+    out_frame: u64,
+    /// seconds into the source
+    source_in: f64,
+    source_out: f64,
+}
+
+/// the timeline of every tile as json, `tiles` must already be clamped to `total_frames`
+pub(crate) fn timeline_json(tiles: &[Vec<Span>], total_frames: u64, fps: f32) -> String {
+    let file = TimelineFile {
+        fps,
+        frames: total_frames,
+        tiles: tiles.iter().enumerate().map(|(i, spans)| TimelineTile {
+            tile: i + 1,
+            clips: spans.iter().map(|s| TimelineClip {
+                src: s.src.clone(),
+                in_frame: s.start,
+                out_frame: s.start + s.frames,
+                source_in: s.source_in,
+                source_out: s.source_in + s.frames as f64 / fps as f64,
+            }).collect(),
+        }).collect(),
+    };
+    serde_json::to_string_pretty(&file).unwrap()
+}
+
+/// `frame` as a non drop frame `hh:mm:ss:ff` timecode, fractional rates use the next whole rate
+fn timecode(frame: u64, fps: f32) -> String {
+    let base = fps.round().max(1.0) as u64;
+    let (secs, ff) = (frame / base, frame % base);
+    format!("{:02}:{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60, ff)
+}
+
+/// a CMX3600 edl of a single tile, each clip is an event with its file name as the clip name
+pub(crate) fn edl(title: &str, spans: &[Span], fps: f32) -> String {
+    let mut txt = format!("TITLE: {}\nFCM: NON-DROP FRAME\n", title);
+    for (i, s) in spans.iter().enumerate() {
+        let source_in = (s.source_in * fps as f64).round() as u64;
+        txt.push_str(&format!(
+            "\n{:03}  AX       V     C        {} {} {} {}\n",
+            i + 1,
+            timecode(source_in, fps), timecode(source_in + s.frames, fps),
+            timecode(s.start, fps), timecode(s.start + s.frames, fps),
+        ));
+        let name = s.src.file_name().unwrap_or_default().to_string_lossy();
+        txt.push_str(&format!("* FROM CLIP NAME: {}\n", name));
+    }
+    txt
+}
+
+/// ffmpeg metadata with a chapter for each clip in `spans`, titled with the clip's file name
+pub(crate) fn ffmetadata(spans: &[Span], fps: f32) -> String {
+    let millis = |frame: u64| (frame as f64 * 1000.0 / fps as f64).round() as u64;
+    let mut txt = ";FFMETADATA1\n".to_string();
+    for s in spans {
+        let name = s.src.file_name().unwrap_or_default().to_string_lossy();
+        let mut title = String::new();
+        for c in name.chars() {
+            if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+                title.push('\\');
+            }
+            title.push(c);
+        }
+        txt.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            millis(s.start), millis(s.start + s.frames), title,
+        ));
+    }
+    txt
+}
+
+/// copy `output` with the chapters in `metadata` (see [ffmetadata]) added, then replace it
+pub(crate) fn embed_chapters(output: &Path, metadata: &Path) -> Result<(), String> {
+    let temp = output.with_file_name(format!("__chapters__{}", output.file_name().unwrap().to_string_lossy()));
+    let mut ffm = FfmpegCommand::new();
+    ffm.args(["-loglevel", "error"])
+        .input(output.to_str().unwrap())
+        .format("ffmetadata")
+        .input(metadata.to_str().unwrap())
+        .args(["-map", "0", "-map_chapters", "1", "-c", "copy", "-y"])
+        .output(temp.to_str().unwrap());
+    let status = ffm.spawn()
+        .and_then(|mut child| child.wait())
+        .map_err(|e| format!("ffmpeg could not be started: {}", e))?;
+    if status.success().not() {
+        let _ = std::fs::remove_file(&temp);
+        return Err(format!("ffmpeg failed with exit code {:?}", status.code()));
+    }
+    std::fs::rename(&temp, output).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audio_follows_frame_starts() {
+        let span = |name: &str, start, frames| Span { src: PathBuf::from(name), source_in: 0.0, start, frames };
+        // a gap after the first clip, the last clip runs past the end of the output
+        let spans = [span("a", 0, 3), span("b", 5, 2), span("c", 7, 10)];
+        let audio = audio_spans(&spans, 10, 29.97, 48000);
+        let samples: Vec<(Option<&str>, u64)> = audio.iter()
+            .map(|a| (a.src.as_ref().map(|s| s.to_str().unwrap()), a.samples))
+            .collect();
+        assert_eq!(samples, vec![(Some("a"), 4804), (None, 3204), (Some("b"), 3203), (Some("c"), 4805)]);
+
+        // hundreds of clips add up to exactly the video length
+        let spans: Vec<Span> = (0..500).map(|i| span("x", i * 7, 7)).collect();
+        let total: u64 = audio_spans(&spans, 3500, 29.97, 44100).iter().map(|a| a.samples).sum();
+        assert_eq!(total, sample_at(3500, 29.97, 44100));
+        assert_eq!(drift_millis(total, 3500, 29.97, 44100), 0.0);
+        assert_eq!(drift_millis(total + 441, 3500, 29.97, 44100), 10.0);
+    }
+
+    #[test]
+    fn timeline_exports() {
+        let spans = clamp(&[
+            Span { src: PathBuf::from("/clips/a=1.mp4"), source_in: 0.0, start: 0, frames: 90 },
+            Span { src: PathBuf::from("/clips/b.mp4"), source_in: 0.0, start: 90, frames: 1900 },
+            Span { src: PathBuf::from("/clips/c.mp4"), source_in: 0.0, start: 2000, frames: 10 },
+        ], 1890);
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[1].frames, 1800);
+
+        let json: serde_json::Value = serde_json::from_str(&timeline_json(std::slice::from_ref(&spans), 1890, 30.0)).unwrap();
+        assert_eq!(json["tiles"][0]["tile"], 1);
+        assert_eq!(json["tiles"][0]["clips"][1]["in_frame"], 90);
+        assert_eq!(json["tiles"][0]["clips"][1]["out_frame"], 1890);
+        assert_eq!(json["tiles"][0]["clips"][1]["source_out"], 60.0);
+
+        assert_eq!(timecode(3600 * 30 + 61 * 30 + 29, 29.97), "01:01:01:29");
+        let edl = edl("out tile 1", &spans, 30.0);
+        assert!(edl.contains("\n002  AX       V     C        00:00:00:00 00:01:00:00 00:00:03:00 00:01:03:00\n"));
+        assert!(edl.ends_with("* FROM CLIP NAME: b.mp4\n"));
+
+        let meta = ffmetadata(&spans, 30.0);
+        assert!(meta.starts_with(";FFMETADATA1\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=3000\ntitle=a\\=1.mp4\n"));
+        assert!(meta.ends_with("START=3000\nEND=63000\ntitle=b.mp4\n"));
+    }
+}
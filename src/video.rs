@@ -1,23 +1,27 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::convert::Into;
 use std::fmt::{Debug, Formatter};
 use std::io::Write;
-use std::ops::{Div, Not, Rem, Sub};
-use std::path::PathBuf;
+use std::ops::{Not, Rem};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use ffmpeg_sidecar;
 use ffmpeg_sidecar::child::FfmpegChild;
-use ffmpeg_sidecar::event::{LogLevel, OutputVideoFrame};
-use ffmpeg_sidecar::iter::FfmpegIterator;
+use ffmpeg_sidecar::event::OutputVideoFrame;
 use ffmpeg_sidecar::{command::FfmpegCommand, event::FfmpegEvent};
-use ffprobe;
 
-use crate::audio::join_audio_video_streams;
-use crate::frame_shape::FrameShape;
-use crate::helper_functions;
+use crate::audio::{audio_track_args, join_audio_video_streams};
+use crate::decoder::{AudioDecoder, Decoder};
+use crate::encoder::{AudioInputs, EncoderError, EncoderSink, CANVAS_COUNT, SAMPLE_BYTES, SAMPLE_RATE};
+use crate::frame_shape::{AudioOverrides, Binding, BlitPool, FrameShape, TileRect};
+use crate::{group_splitter, helper_functions};
 use crate::helper_functions::{iter_ffmpeg_events, seconds_to_hhmmss, MultiPathBuf};
-use crate::switches::SortOrder;
+use crate::focus::Focus;
+use crate::probe::{self, ProbeInfo};
+use crate::playlist::{Entry, Trim};
+use crate::switches::{AudioFocus, AudioMode, AudioTracks, FitMode, OnExhaust, SortOrder};
+use crate::timeline::{audio_spans, clamp, drift_millis, edl, embed_chapters, ffmetadata, sample_at, timeline_json, Span};
 
 
 //noinspection SpellCheckingInspection
@@ -31,266 +35,95 @@ const DECODER: [&str; 2] = ["-hwaccel", "auto"];
 // opencl   Error
 // vulkan   Error
 
+/// seconds before the end of a clip at which the next clip's decoders are started, enough for
+/// ffmpeg to start and fill its frame buffer without keeping two processes per tile for a whole clip
+const PREFETCH_SECS: f64 = 2.0;
+
+/// sample rate of the per clip wavs exported for `--two-pass-audio`
+const EXPORT_SAMPLE_RATE: u32 = 44100;
 
 #[derive(Clone)]
 pub(crate) struct VideoEditData {
     pub(crate) output_height: u32,
     pub(crate) output_width: u32,
-    shapes: Vec<(u32, u32)>,
+    /// tile positions in the output frame, see [FrameShape::tile_rects]
+    pub(crate) rects: Vec<TileRect>,
     fps: f32,
+    /// fit mode used for tiles without an entry in `fit_groups`
+    fit: FitMode,
+    /// per group fit mode, keyed by tile position
+    fit_groups: HashMap<usize, FitMode>,
 }
 
 impl VideoEditData {
     pub fn init() -> VideoEditData {
-        let shapes = vec![(5, 5); 25];
+        let rects = vec![TileRect { x: 0, y: 0, w: 5, h: 5 }; 25];
         VideoEditData {
             output_height: 1440,
             output_width: 850 * 3,
-            shapes,
+            rects,
             fps: 30f32,
+            fit: FitMode::Crop,
+            fit_groups: HashMap::new(),
         }
     }
     pub fn init_wxh(w: u32, h: u32, count: u32) -> VideoEditData {
-        let shapes = (0..count)
+        let rects = (0..count)
             .into_iter()
-            .map(|_| (w.clone() / count.clone(), h.clone()))
+            .map(|i| TileRect { x: i * (w / count), y: 0, w: w / count, h })
             .collect();
 
         VideoEditData {
             output_height: h,
             output_width: w,
-            shapes,
+            rects,
             fps: 30f32,
+            fit: FitMode::Crop,
+            fit_groups: HashMap::new(),
         }
     }
 
     #[allow(unused)]
     pub fn init_abc_shape(widths: Vec<u32>, h: u32) -> VideoEditData {
-        let shapes = widths.iter().map(|f| (f.clone(), h.clone())).collect();
+        let mut x = 0;
+        let rects = widths.iter().map(|f| {
+            x += f;
+            TileRect { x: x - f, y: 0, w: *f, h }
+        }).collect();
         VideoEditData {
             output_height: h,
             output_width: widths.iter().sum(),
-            shapes,
+            rects,
             fps: 30f32,
+            fit: FitMode::Crop,
+            fit_groups: HashMap::new(),
         }
     }
     pub fn set_fps(&mut self, fps: f32) {
         self.fps = fps
     }
-    fn get_shapes(&self) -> &[(u32, u32)] {
-        self.shapes.as_slice()
+    pub(crate) fn set_fit(&mut self, fit: FitMode, fit_groups: HashMap<usize, FitMode>) {
+        self.fit = fit;
+        self.fit_groups = fit_groups;
+    }
+    fn get_shape(&self, position: usize) -> (u32, u32) {
+        let r = self.rects[position];
+        (r.w, r.h)
+    }
+    fn get_fit(&self, position: usize) -> FitMode {
+        self.fit_groups.get(&position).unwrap_or(&self.fit).clone()
     }
 
 
     pub(crate) fn set_shape(&mut self, shaper: FrameShape) {
-        match shaper {
-            FrameShape::Mono => {
-                self.shapes = vec![
-                    (self.output_width, self.output_height),
-                ]
-            }
-            FrameShape::Dual => {
-                self.shapes = vec![
-                    (self.output_width / 2, self.output_height),
-                    (self.output_width - self.output_width / 2, self.output_height),
-                ];
-            }
-            FrameShape::Triple => {
-                let ow = self.output_width / 3;
-                self.shapes = vec![
-                    (ow, self.output_height),
-                    (self.output_width - ow - ow, self.output_height),
-                    (ow, self.output_height),
-                ];
-            }
-            FrameShape::Quad => {
-                let (w1, h1) = (self.output_width / 2, self.output_height / 2);
-                let (w2, h2) = (self.output_width - w1, self.output_height - h1);
-                self.shapes = vec![
-                    (w1, h1),
-                    (w2, h1),
-                    (w1, h2),
-                    (w2, h2),
-                ];
-            }
-            FrameShape::VertEmph => {
-                let w23 = self.output_width / 3;
-                let h1 = self.output_height / 2;
-                let h2 = self.output_height - h1;
-                self.shapes = vec![
-                    (self.output_width - w23 - w23, self.output_height),
-                    (w23, h1),
-                    (w23, h1),
-                    (w23, h2),
-                    (w23, h2),
-                ];
-            }
-            FrameShape::HorizEmph => {
-                let w23 = self.output_width / 3;
-                let h1 = self.output_height / 2;
-                self.shapes = vec![
-                    (w23, self.output_height),
-                    (self.output_width - w23 - w23, h1),
-                    (self.output_width - w23 - w23, self.output_height - h1),
-                    (w23, self.output_height),
-                ];
-            }
-            FrameShape::VertEmph2 => {
-                let owx = self.output_width.rem(8);
-                let ow = self.output_width.sub(owx).div(8);
-
-                self.shapes = vec![
-                    (ow * 2 + owx, self.output_height),
-                    (ow * 3, self.output_height / 2),
-                    (ow * 3, self.output_height / 2),
-                    (ow * 3, self.output_height - self.output_height / 2),
-                    (ow * 3, self.output_height - self.output_height / 2),
-                ];
-            }
-            FrameShape::HorizEmph2 => {
-                let owx = self.output_width.rem(4);
-                let ow = self.output_width.sub(owx).div(4);
-                self.shapes = vec![
-                    (ow, self.output_height),
-                    (ow * 2 + owx, self.output_height / 2),
-                    (ow * 2 + owx, self.output_height - self.output_height / 2),
-                    (ow, self.output_height),
-                ];
-            }
-            FrameShape::SideVert | FrameShape::SideVert2 => {
-                let owx = self.output_width.rem(3);
-                let ow = self.output_width.sub(owx).div(3);
-                self.shapes = vec![
-                    (ow, self.output_height),
-                    (ow * 2 + owx, self.output_height / 2),
-                    (ow * 2 + owx, self.output_height - self.output_height / 2),
-                ];
-            }
-            FrameShape::CentreEmphVert | FrameShape::CentreEmphVert2 => {
-                let wmid = self.output_width / 5 * 3;
-                let w_l = (self.output_width - wmid) / 2;
-                let w_r = self.output_width - wmid - w_l;
-
-                let wml = wmid / 2;
-                let wmr = wmid - wml;
-
-                let htop = self.output_height / 5 * 3;
-                let hbot = self.output_height - htop;
-
-                assert_eq!(wmid + w_l + w_r, self.output_width);
-                assert_eq!(wml + wmr + w_l + w_r, self.output_width);
-                assert_eq!(htop + hbot, self.output_height);
-
-                self.shapes = vec![
-                    (wmid, htop),
-                    (w_l, self.output_height),
-                    (w_r, self.output_height),
-                    (wml, hbot),
-                    (wmr, hbot),
-                ]
-            }
-            FrameShape::MoreHoriz | FrameShape::MoreHoriz2 => {
-                let wmid = self.output_width / 5 * 3;
-                let w_l = (self.output_width - wmid) / 2;
-                let w_r = self.output_width - wmid - w_l;
-
-                let wml = wmid / 2;
-                let wmr = wmid - wml;
-
-                let h_mid_big = self.output_height / 5 * 3;
-                let h_mid_small = self.output_height - h_mid_big;
-
-                let h_side_big = self.output_height / 3 * 2;
-                let h_side_small = self.output_height - h_side_big;
-
-                assert_eq!(wmid + w_l + w_r, self.output_width);
-                assert_eq!(wml + wmr + w_l + w_r, self.output_width);
-                assert_eq!(h_mid_big + h_mid_small, self.output_height);
-
-                self.shapes = vec![
-                    (wmid, h_mid_big),
-                    (w_l, h_side_big),
-                    (w_r, h_side_big),
-                    (wml, h_mid_small),
-                    (wmr, h_mid_small),
-                    (w_l, h_side_small),
-                    (w_r, h_side_small),
-                ]
-            }
-            FrameShape::ExtendedLandscape => {
-                let v_1_3_a = self.output_height / 3;
-                let v_1_3_b = self.output_height / 3;
-                let v_1_3_c = self.output_height - v_1_3_a - v_1_3_b;
-
-                let wmid = self.output_width / 5 * 3;
-                let w_l = (self.output_width - wmid) / 2;
-                let w_r = self.output_width - wmid - w_l;
-
-                let wml = wmid / 2;
-                let wmr = wmid - wml;
-
-                let h_mid_big = self.output_height / 5 * 3;
-                let h_mid_small = self.output_height - h_mid_big;
-
-
-                self.shapes = vec![
-                    (wmid, h_mid_big),
-                    (wml, h_mid_small),
-                    (wmr, h_mid_small),
-                    (w_l, v_1_3_a),
-                    (w_l, v_1_3_b),
-                    (w_l, v_1_3_c),
-                    (w_r, v_1_3_a),
-                    (w_r, v_1_3_b),
-                    (w_r, v_1_3_c),
-                ]
-            }
-            FrameShape::ExtendedLandscape2 => {
-                let horizontal_thirds = self.output_width / 3;
-                let horizontal_thirds_mid = self.output_width - horizontal_thirds - horizontal_thirds;
-
-                let vertical_top_thirds = self.output_height / 3;
-                let vertical_thirds = self.output_height - vertical_top_thirds - vertical_top_thirds;
-
-                let wmid = self.output_width / 5 * 3;
-                let w_l = (self.output_width - wmid) / 2;
-                let w_r = self.output_width - wmid - w_l;
-
-                self.shapes = vec![
-                    (wmid, vertical_top_thirds + vertical_top_thirds),
-                    (horizontal_thirds, vertical_thirds),
-                    (horizontal_thirds_mid, vertical_thirds),
-                    (horizontal_thirds, vertical_thirds),
-                    (w_l, vertical_top_thirds),
-                    (w_r, vertical_top_thirds),
-                    (w_l, vertical_top_thirds),
-                    (w_r, vertical_top_thirds),
-                ]
-            }
-            FrameShape::OffsetVH4x4 => {
-                let horizontal_023 = self.output_width / 4;
-                let horizontal_1 = self.output_width - horizontal_023 * 3;
-                let height_min = self.output_height / 4;
-                let height_max = self.output_height - height_min;
-                self.shapes = vec![
-                    (horizontal_1, height_min),
-                    (horizontal_023, height_min),
-                    (horizontal_023, height_min),
-                    (horizontal_023, height_min),
-                    (horizontal_023, height_max),
-                    (horizontal_023, height_max),
-                    (horizontal_1, height_max),
-                    (horizontal_023, height_max),
-                ]
-            }
-        }
+        self.rects = shaper.tile_rects(self.output_width, self.output_height);
     }
 }
 
 #[cfg(test)]
 impl PartialEq for VideoEditData {
     fn eq(&self, other: &Self) -> bool {
-        for (a, b) in self.shapes.iter().zip(other.shapes.iter()) {
+        for (a, b) in self.rects.iter().zip(other.rects.iter()) {
             if a != b {
                 return false;
             }
@@ -307,12 +140,22 @@ impl PartialEq for VideoEditData {
 
 pub(crate) struct Video {
     pub(crate) src: PathBuf,
-    frame_iterable: Option<FfmpegIterator>,
+    /// running decoder, started ahead of time by [VideoList::prefetch]
+    decoder: Option<Decoder>,
+    /// live audio, started alongside the video decoder
+    audio: Option<AudioDecoder>,
     pub(crate) length_millis: Option<i64>,
+    /// length, size and streams of the clip, see [Video::probe_info]
+    probe: Option<ProbeInfo>,
+    /// part of the clip played, from a playlist's in and out points
+    trim: Trim,
     frame_count: u64,
     frame_start: u64,
+    /// seconds into the clip to start decoding from, set when the tile is re-sized mid clip
+    resume_at: Option<f64>,
     width_height: (u32, u32),
     fps: f32,
+    fit: FitMode,
 }
 
 impl Video {
@@ -321,105 +164,113 @@ impl Video {
         helper_functions::parse_debug(" Video from Path ", file!(), line!());
         Video {
             src: src.into(),
-            frame_iterable: None,
+            decoder: None,
+            audio: None,
             length_millis: None,
+            probe: None,
+            trim: Trim::default(),
             frame_count: 0u64,
             frame_start: 0u64,
+            resume_at: None,
             width_height: (0, 0),
             fps: 0f32,
+            fit: FitMode::Crop,
+        }
+    }
+    /// a fresh copy of this video for playing it again, probe data and tile settings are kept
+    fn replay(&self) -> Video {
+        Video {
+            src: self.src.clone(),
+            decoder: None,
+            audio: None,
+            length_millis: self.length_millis,
+            probe: self.probe.clone(),
+            trim: self.trim,
+            frame_count: 0u64,
+            frame_start: 0u64,
+            resume_at: None,
+            width_height: self.width_height,
+            fps: self.fps,
+            fit: self.fit.clone(),
         }
     }
-    fn set_video_shape(&mut self, wxh: (u32, u32), fps: f32) {
+    /// play only `trim` of the clip
+    pub(crate) fn with_trim(mut self, trim: Trim) -> Video {
+        self.trim = trim;
+        self
+    }
+
+    fn set_video_shape(&mut self, wxh: (u32, u32), fps: f32, fit: FitMode) {
         self.width_height = wxh;
         self.fps = fps;
+        self.fit = fit;
     }
 
 
-    //noinspection SpellCheckingInspection
-    fn audio_export_proc_out(&self, out: &PathBuf) -> (bool, Option<FfmpegChild>) {
-        if self.frame_count < 1 {
-            return (false, None);
-        };
-
-        let tar = self.src.clone();
-        let length = format!("{:.6}s", (self.frame_count.clone() as f32) / &self.fps);
-
-        let mut ffm = FfmpegCommand::new();
-        let ffm = ffm.input(tar.to_str().unwrap()).no_video();
-        let ffm = ffm.filter(format!(
-            "[0:a]apad=whole_dur={}[a]",
-            length
-        ));
-        let ffm = ffm
-            .args([
-                "-t", &length,
-                "-ar", "44100"
-            ])
-            .arg("-y")
-            .output(out.to_str().unwrap());
+    /// ffprobe data of the clip, probed once and shared with later runs through the probe cache
+    fn probe_info(&mut self) -> Result<&ProbeInfo, &'static str> {
+        if self.probe.is_none() {
+            self.probe = Some(probe::probe(&self.src).map_err(|_| "ffprobe failed")?);
+        }
+        Ok(self.probe.as_ref().unwrap())
+    }
 
-        let complete = ffm.spawn().unwrap();
-        (true, Some(complete))
+    /// true if the clip has at least one audio stream, clips that cannot be probed are treated as silent
+    pub(crate) fn has_audio(&mut self) -> bool {
+        self.probe_info().is_ok_and(|p| p.has_audio)
     }
 
     //noinspection SpellCheckingInspection
     pub(crate) fn get_length(&mut self) -> Result<i64, &str> {
         #[cfg(feature = "hyperDebug")]
         helper_functions::parse_debug(" get length ", file!(), line!());
-        match self.length_millis {
-            None => {
-                let info = match ffprobe::ffprobe(self.src.clone()) {
-                    Ok(info) => info,
-                    Err(_) => {
-                        return Err("ffprobe failed");
-                    }
-                };
-                match info.format.try_get_duration() {
-                    None => {
-                        return Err("failed to find duration");
-                    }
-                    Some(Err(_)) => {
-                        return Err("float parse error for duration");
-                    }
-                    Some(Ok(t)) => self.length_millis = Some(t.as_millis() as i64),
-                }
-            }
-            Some(_) => {}
+        if self.length_millis.is_none() {
+            let full = self.probe_info()?.duration_millis.ok_or("failed to find duration")?;
+            self.length_millis = Some(self.trim.length_millis(full));
         }
-        Ok(self.length_millis.unwrap().clone())
+        Ok(self.length_millis.unwrap())
     }
 
-    //noinspection SpellCheckingInspection
-    fn setup_video(&mut self) {
-        #[cfg(feature = "hyperDebug")]
-        helper_functions::parse_debug(" setup_video ", file!(), line!());
+    /// width and height of the first video stream as it is displayed,
+    /// clips with a 90 or 270 degree rotation have their width and height swapped
+    pub(crate) fn get_display_size(&mut self) -> Result<(u32, u32), &str> {
+        self.probe_info()?.display_size.ok_or("failed to find video dimensions")
+    }
 
-        #[cfg(feature = "hyperDebug")]
-        helper_functions::parse_debug(format!("crop=w={:?}:h={:?} fps={}", self.width_height.0,
-                                              self.width_height.1, self.fps).as_str(),
-                                      file!(), line!());
+    /// true if the clip is taller than it is wide, false if it is not or cannot be probed
+    pub(crate) fn is_portrait(&mut self) -> bool {
+        match self.get_display_size() {
+            Ok((w, h)) => h > w,
+            Err(_) => false,
+        }
+    }
+
+    /// ffmpeg command decoding this clip into raw frames at the tile size,
+    /// decoding starts `seek` seconds into the clip's trim when given and stops at its out point
+    fn decoder_command(&self, seek: Option<f64>) -> FfmpegCommand {
         let filtergraph = {
-            let scaler = format!("scale={:?}:{:?}:force_original_aspect_ratio=increase",
-                                 self.width_height.0, self.width_height.1);
-            let crop = format!("crop=w={:?}:h={:?}",
-                               self.width_height.0, self.width_height.1);
+            let fit = self.fit.filtergraph(self.width_height.0, self.width_height.1);
             let fps = format!("fps=fps={}", self.fps);
-            format!("[0:v]{}[a];[a]{}[b];[b]{}[output]",
-                    scaler,
-                    crop,
+            format!("{};[b]{}[output]",
+                    fit,
                     fps,
             )
         };
 
         let mut ffm = FfmpegCommand::new();
-        let ffm = if DECODER[1].len() > 2 {
-            ffm.args(DECODER)
-        } else { &mut ffm };
-        let ffm = ffm.input(self.src.to_str().unwrap());
-        let ffm = ffm.filter(filtergraph);
-        let ffm = ffm.rawvideo();
-        #[cfg(feature = "hyperDebug")]
-        let ffm = ffm.print_command();
+        if DECODER[1].len() > 2 {
+            ffm.args(DECODER);
+        }
+        let start = self.trim.start + seek.unwrap_or(0.0);
+        if start > 0.0 {
+            ffm.seek(format!("{:.3}", start));
+        }
+        if let Some(end) = self.trim.end {
+            ffm.duration(format!("{:.3}", (end - start).max(0.0)));
+        }
+        ffm.input(self.src.to_str().unwrap())
+            .filter(filtergraph)
+            .rawvideo();
         // for debugging ffmpeg bits, this will push the report per file into the /ffreport/ folder
 
         #[cfg(feature = "ffmpegReport")]
@@ -432,84 +283,60 @@ impl Video {
                 self.src.file_name().unwrap().to_str().unwrap()
             ).as_str(),
         );
+        ffm
+    }
 
-        self.frame_iterable = Some(ffm.spawn().unwrap().iter().unwrap());
+    //noinspection SpellCheckingInspection
+    fn setup_video(&mut self) {
+        #[cfg(feature = "hyperDebug")]
+        helper_functions::parse_debug(" setup_video ", file!(), line!());
+
+        #[cfg(feature = "hyperDebug")]
+        helper_functions::parse_debug(format!("crop=w={:?}:h={:?} fps={}", self.width_height.0,
+                                              self.width_height.1, self.fps).as_str(),
+                                      file!(), line!());
+        let resume_at = self.resume_at.take();
+        let ffm = self.decoder_command(resume_at);
+        self.decoder = Some(Decoder::spawn(ffm, self.src.clone(), self.width_height, self.fps));
 
         eprintln!("Begin Frames: {:?}", self.src)
     }
 
-    pub fn next_frame(&mut self) -> Option<OutputVideoFrame> {
-        #[cfg(feature = "hyperDebug")]
-        helper_functions::parse_debug(" Video::next_frame() ", file!(), line!());
-        for _ in 0..1000 {
-            // frames aren't always a frame, only return a Frame or None
-            // setup if no video currently setup
-            if self.frame_iterable.is_none() {
-                self.setup_video();
-                continue;
-            }
+    /// append this clip's audio for the next `samples` samples to `out`
+    fn next_audio(&mut self, samples: usize, out: &mut Vec<u8>) {
+        self.audio.get_or_insert_with(|| AudioDecoder::spawn(&self.src, self.trim.start)).read(samples, out)
+    }
 
-            // grab the next frame without dropping it from the Option<>
-            let frame = self.frame_iterable.as_mut().map(|f| f.next())?;
+    /// decode the single frame shown `seek` seconds into the clip, used for previews
+    pub(crate) fn frame_at(&self, seek: f64) -> Option<OutputVideoFrame> {
+        let mut child = self.decoder_command(Some(seek)).spawn().ok()?;
+        let frame = child.iter().ok()?.find_map(|e| match e {
+            FfmpegEvent::OutputFrame(frame) => Some(frame),
+            _ => None,
+        });
+        // only the first frame is needed
+        let _ = child.kill();
+        let _ = child.wait();
+        frame
+    }
 
-            // if it doesn't return a frame it's probably done for
-            let frame = match frame {
-                None => {
-                    return None;
-                }
-                Some(f) => f,
-            };
-            // return frame if the item is a frame, otherwise print updates
-            match frame {
-                FfmpegEvent::OutputFrame(frame) => {
-                    self.frame_count += 1;
-                    return Some(frame);
-                }
-                // FfmpegEvent::ParsedStreamMapping(m) => {
-                //     eprintln!("ParsedStreamMapping: {}", m);
-                // }
-                FfmpegEvent::Done => {
-                    eprintln!("Done: {:?}", self.src)
-                }
-                FfmpegEvent::Log(level, data) => {
-                    match level {
-                        LogLevel::Info => {
-                            #[cfg(feature = "hyperDebug")]
-                            helper_functions::parse_debug("LogLevel::Info", file!(), line!());
-                            #[cfg(feature = "hyperDebug")]
-                            eprintln!("LOG {:?} : {}", level, data);
-                        }
-                        LogLevel::Warning | LogLevel::Error | LogLevel::Fatal => {
-                            eprintln!("LOG {:?} : {}  --  {:?}", level, data, self.src);
-                            // return None;
-                        }
-                        LogLevel::Unknown => {
-                            if data.len() > 2 {
-                                eprintln!("LOG {:?} : {}", level, data)
-                            }
-                        }
-                    }
-                }
-                // Ignored events
-                #[cfg(feature = "hyperDebug")]
-                FfmpegEvent::ParsedConfiguration(f) => {
-                    eprintln!("LOG {:?} ", f);
-                }
-                #[cfg(feature = "hyperDebug")]
-                FfmpegEvent::ParsedVersion(f) => {
-                    eprintln!("LOG {:?} ", f);
-                }
-                #[cfg(feature = "hyperDebug")]
-                FfmpegEvent::Progress(f) => {
-                    eprintln!("LOG {:?} ", f);
-                }
+    /// true once the clip has at most `PREFETCH_SECS` left to play, or if its length is unknown
+    fn near_end(&self) -> bool {
+        match self.length_millis {
+            Some(length) => length as f64 / 1000.0 - self.frame_count as f64 / self.fps as f64 <= PREFETCH_SECS,
+            None => true,
+        }
+    }
 
-                _ => {
-                    //eprintln!("___ {:?}", frame)
-                }
-            }
+    pub fn next_frame(&mut self) -> Option<OutputVideoFrame> {
+        #[cfg(feature = "hyperDebug")]
+        helper_functions::parse_debug(" Video::next_frame() ", file!(), line!());
+        if self.decoder.is_none() {
+            self.setup_video();
         }
-        None
+        let frame = self.decoder.as_ref()?.next_frame()?;
+        self.frame_count += 1;
+        Some(frame)
     }
 }
 
@@ -517,8 +344,14 @@ struct VideoList {
     videos: VecDeque<Video>,
 
     complete_videos: VecDeque<Video>,
+    /// first video in `complete_videos` of the current pass, see [VideoList::restart]
+    pass_start: usize,
     video_sizer: VideoEditData,
     position: u32,
+    /// decode audio alongside the video, see [AudioMode::Live]
+    live_audio: bool,
+    /// live audio samples sent to the encoder, checked against the video length once encoded
+    samples_sent: u64,
 }
 
 
@@ -531,8 +364,11 @@ impl VideoList {
         let mut vl = VideoList {
             videos: VecDeque::from(videos),
             complete_videos: VecDeque::with_capacity(vid_count),
+            pass_start: 0,
             video_sizer: VideoEditData::init(),
             position: pos,
+            live_audio: false,
+            samples_sent: 0,
         };
 
         vl.set_video_sizer(vl.video_sizer.clone());
@@ -542,31 +378,51 @@ impl VideoList {
         #[cfg(feature = "hyperDebug")]
         helper_functions::parse_debug("Setting VideoSizer in VideList", file!(), line!());
         self.video_sizer = vs;
-        let vid_shape = self.video_sizer.get_shapes()[self.position as usize];
+        let vid_shape = self.video_sizer.get_shape(self.position as usize);
+        let fit = self.video_sizer.get_fit(self.position as usize);
         for v in self.videos.iter_mut() {
-            v.set_video_shape(vid_shape, self.video_sizer.fps)
+            v.set_video_shape(vid_shape, self.video_sizer.fps, fit.clone())
         }
     }
 
-    fn cheap_audio_exporter_out_proc(&mut self, grp: usize, temp_folder: &PathBuf) -> Vec<PathBuf> {
+    /// clips played so far in output order, including the one playing, `frame_start` is counted from 1
+    fn timeline(&self) -> Vec<Span> {
+        self.complete_videos.iter()
+            .chain(self.videos.front())
+            .filter(|v| v.frame_count > 0)
+            .map(|v| Span { src: v.src.clone(), source_in: v.trim.start, start: v.frame_start - 1, frames: v.frame_count })
+            .collect()
+    }
+
+    /// export the group's audio as wavs placed by the timeline, together exactly `total_frames` long
+    fn cheap_audio_exporter_out_proc(&mut self, grp: usize, temp_folder: &Path, total_frames: u64) -> Vec<PathBuf> {
+        let silent: Vec<PathBuf> = self.complete_videos.iter_mut()
+            .filter_map(|v| (v.frame_count > 0 && v.has_audio().not()).then(|| v.src.clone()))
+            .collect();
+        let spans = audio_spans(&self.timeline(), total_frames, self.video_sizer.fps, EXPORT_SAMPLE_RATE);
+
         let mut outputs = vec![];
         let mut out_proc = vec![];
-        for (i, vid) in self.complete_videos.iter().enumerate() {
-            println!("Audio Export: {}", vid.src.clone().to_str().unwrap());
-            let out = temp_folder.clone().join(format!("g{}f{}.wav", grp, i));
-            match vid.audio_export_proc_out(&out) {
-                (false, _) => {}
-                (true, Some(proc)) => {
-                    outputs.push(out);
-                    out_proc.push(proc);
-                }
-                (_, _) => {}
+        for (i, span) in spans.iter().enumerate() {
+            let src = span.src.as_ref().filter(|s| silent.contains(s).not());
+            match src {
+                Some(src) => println!("Audio Export: {}", src.to_str().unwrap()),
+                None => println!("Audio Export: {} samples of silence", span.samples),
             }
+            let out = temp_folder.join(format!("g{}f{}.wav", grp, i));
+            out_proc.push(audio_export_proc_out(src, span.source_in, span.samples, &out));
+            outputs.push(out);
         }
         for i in out_proc.iter_mut() {
             iter_ffmpeg_events(i);
             i.wait().unwrap();
         }
+
+        let exported = outputs.iter().map(PathBuf::as_path).map(wav_samples).sum::<Option<u64>>();
+        match exported {
+            Some(samples) => report_drift(grp, samples, total_frames, self.video_sizer.fps, EXPORT_SAMPLE_RATE),
+            None => println!("Audio drift tile {}: exported audio could not be probed", grp + 1),
+        }
         outputs
     }
 
@@ -577,7 +433,13 @@ impl VideoList {
                 #[cfg(feature = "hyperDebug")]
                 println!("Error matching front video, total videos {}", self.videos.len())
             }
-            Some(v) => { v.frame_start = fc.clone() }
+            // the clip's position in the output, see [VideoList::timeline],
+            // its length tells [VideoList::prefetch] when to start the next clip
+            Some(v) if v.frame_count == 0 => {
+                v.frame_start = *fc;
+                let _ = v.get_length();
+            }
+            Some(_) => {}
         }
         for _ in 0..10 {
 
@@ -593,9 +455,12 @@ impl VideoList {
                 None => {
                     match self.videos.pop_front() {
                         Some(vid) => {
-                            self.complete_videos.push_back(vid);
+                            self.complete(vid);
                             match self.videos.front_mut() {
-                                Some(fm) => fm.frame_start = fc.clone(),
+                                Some(fm) => {
+                                    fm.frame_start = *fc;
+                                    let _ = fm.get_length();
+                                }
                                 None => {}
                             };
                         }
@@ -607,6 +472,7 @@ impl VideoList {
                     continue;
                 }
                 Some(f) => {
+                    self.prefetch();
                     return Some(f);
                 }
             }
@@ -617,10 +483,106 @@ impl VideoList {
 
         None
     }
+    /// start decoding the clip after the current one once the current clip is near its end,
+    /// so it is ready as soon as the current clip ends
+    fn prefetch(&mut self) {
+        if self.videos.front().is_some_and(Video::near_end).not() {
+            return;
+        }
+        if let Some(v) = self.videos.get_mut(1) {
+            if v.decoder.is_none() {
+                v.setup_video();
+            }
+            if self.live_audio && v.audio.is_none() {
+                v.audio = Some(AudioDecoder::spawn(&v.src, v.trim.start));
+            }
+        }
+    }
+
+    /// audio of the clip currently playing for the next `samples` samples, silence once the list is empty
+    fn next_audio(&mut self, samples: usize, out: &mut Vec<u8>) {
+        match self.videos.front_mut() {
+            Some(v) => v.next_audio(samples, out),
+            None => out.resize(out.len() + samples * SAMPLE_BYTES, 0),
+        }
+    }
+
+    /// return a frame's buffer to the clip currently decoding
+    fn recycle(&self, frame: OutputVideoFrame) {
+        if let Some(d) = self.videos.front().and_then(|v| v.decoder.as_ref()) {
+            d.recycle(frame.data);
+        }
+    }
+
+    /// stop the decoders of clips that were not played to the end
+    fn stop_decoders(&mut self) {
+        for v in self.videos.iter_mut().chain(self.complete_videos.iter_mut()) {
+            v.decoder = None;
+            v.audio = None;
+        }
+    }
+
+    /// move a finished video to `complete_videos`, its ffmpeg processes are stopped so
+    /// a long render does not keep one open per clip played
+    fn complete(&mut self, mut vid: Video) {
+        vid.decoder = None;
+        vid.audio = None;
+        self.complete_videos.push_back(vid);
+    }
+
+    /// queue a replay of every video played since the last restart,
+    /// returns false if none of them produced any frames
+    fn restart(&mut self) -> bool {
+        let replays: Vec<Video> = self.complete_videos.iter()
+            .skip(self.pass_start)
+            .filter(|v| v.frame_count > 0)
+            .map(|v| v.replay())
+            .collect();
+        self.pass_start = self.complete_videos.len();
+        self.videos.extend(replays);
+        self.videos.is_empty().not()
+    }
+
+    /// move this list to tile `position` of `vs`, a clip that is part way through is
+    /// restarted at the new size from the frame it had reached
+    fn reshape(&mut self, position: u32, vs: VideoEditData) {
+        self.position = position;
+        self.set_video_sizer(vs);
+        if let Some(v) = self.videos.front_mut() {
+            v.decoder = None;
+            v.resume_at = (v.frame_count > 0).then(|| v.frame_count as f64 / v.fps as f64);
+        }
+        // a prefetched clip was started at the old tile size
+        if let Some(v) = self.videos.get_mut(1) {
+            v.decoder = None;
+        }
+    }
+
+    /// count the last frame as not shown, it is decoded again after the next [VideoList::reshape]
+    fn rewind(&mut self) {
+        if let Some(v) = self.videos.front_mut() {
+            v.frame_count = v.frame_count.saturating_sub(1);
+        }
+    }
+
+    /// frame shown `time_millis` into this list, or None if the list is shorter than that
+    fn frame_at(&mut self, time_millis: i64) -> Option<OutputVideoFrame> {
+        let mut start = 0i64;
+        for v in self.videos.iter_mut() {
+            let length = v.get_length().unwrap_or(0);
+            if time_millis < start + length {
+                println!("Preview tile {}: {} at {:.3}s", self.position + 1, v.src.to_str().unwrap(),
+                         (time_millis - start) as f64 / 1000.0);
+                return v.frame_at((time_millis - start) as f64 / 1000.0);
+            }
+            start += length;
+        }
+        None
+    }
     fn pop_first_vid(&mut self) -> bool {
         match self.videos.pop_front() {
             Some(vid) => {
-                self.complete_videos.push_back(vid);
+                self.complete(vid);
                 true
             }
             None => false,
@@ -633,25 +595,50 @@ pub struct VideoGroup {
     output_target: PathBuf,
     video_sizer: VideoEditData,
     shape_style: FrameShape,
+    on_exhaust: OnExhaust,
+    /// output ends after this many frames, see [VideoGroup::set_duration]
+    frame_limit: Option<u64>,
+    audio_focus: AudioFocus,
+    audio_overrides: AudioOverrides,
+    audio_tracks: AudioTracks,
+    /// write an edl per tile alongside the timeline, see [VideoGroup::write_timeline]
+    export_edl: bool,
+    /// frames in the encoded video, the audio is built to exactly this length
+    frames_written: u64,
+    /// group shown in each tile, changed when groups are reflowed out
+    slots: Vec<usize>,
+    /// group whose clips are added as chapters, the hero before any reflow
+    chapter_hero: usize,
 }
 
 impl VideoGroup {
     pub(crate) fn print_time(&mut self, extra_info: bool) {
         let mut min_len = i64::MAX;
+        let mut max_len = 0;
         for (x, vid) in self.videos.iter_mut().enumerate() {
             let len = vid.videos.iter_mut().fold(0, |a, f| {
                 a + f.get_length().unwrap_or(0)
             }) / 1000;
             if len < min_len { min_len = len }
+            if len > max_len { max_len = len }
             println!("Video: {} length: {}", x, seconds_to_hhmmss(len as u64));
         }
-        println!("end video length: {}", seconds_to_hhmmss(min_len as u64));
+        // only `stop` ends with the shortest group, the other policies run until the longest ends
+        let end_len = match (self.frame_limit, &self.on_exhaust) {
+            (Some(frames), _) => (frames as f32 / self.video_sizer.fps) as i64,
+            (None, OnExhaust::Stop) => min_len,
+            (None, _) => max_len,
+        };
+        println!("end video length: {}", seconds_to_hhmmss(end_len as u64));
 
         if !extra_info { return; }
         for (x, vid) in self.videos.iter_mut().enumerate() {
             println!("Video Group: {}", x);
             for v in vid.videos.iter() {
-                println!("\t{}", v.src.to_str().unwrap());
+                match &v.probe {
+                    Some(p) => println!("\t{}  {}", v.src.to_str().unwrap(), p),
+                    None => println!("\t{}", v.src.to_str().unwrap()),
+                }
             }
         }
     }
@@ -663,11 +650,16 @@ impl VideoGroup {
         src_out: impl Into<PathBuf>,
         screens: FrameShape,
         sorter: SortOrder,
+        orient_tiles: Option<&[TileRect]>,
     ) -> VideoGroup {
         #[cfg(feature = "hyperDebug")]
         helper_functions::parse_debug("new_from_folder", file!(), line!());
 
-        let videos = helper_functions::video_group_swap_n(src, screens.clone().count() as usize);
+        // route clips to tiles by orientation when the tile positions are known
+        let videos = match orient_tiles {
+            Some(tiles) => helper_functions::video_group_orient(src, tiles),
+            None => helper_functions::video_group_swap_n(src, screens.clone().count() as usize),
+        };
 
         // setup group for exporting
         VideoGroup {
@@ -678,208 +670,140 @@ impl VideoGroup {
                 .collect(),
             output_target: src_out.into(),
             video_sizer: VideoEditData::init(),
+            slots: (0..screens.count() as usize).collect(),
             shape_style: screens,
+            on_exhaust: OnExhaust::Stop,
+            frame_limit: None,
+            audio_focus: AudioFocus::All,
+            audio_overrides: AudioOverrides::default(),
+            audio_tracks: AudioTracks::Mix,
+            export_edl: false,
+            frames_written: 0,
+            chapter_hero: 0,
         }
     }
 
-    //noinspection DuplicatedCode
     pub fn new_from_folders(
         srcs: Vec<MultiPathBuf>,
         src_out: impl Into<PathBuf>,
         screens: FrameShape,
         sorter: SortOrder,
     ) -> VideoGroup {
-        // Special cases for vertical and horuizontal input groups
-        let vid_lists: Vec<VideoList> = match (screens.clone(), srcs.len()) {
-            (FrameShape::VertEmph, 2) | (FrameShape::VertEmph2, 2) => {
-                // vertical parts
-                let videos1 = VideoList::from_videos(helper_functions::scan_dir_for_videos(srcs[0].clone()), 0, sorter.clone());
-                // horizontal parts
-                let mut videos2 = helper_functions::video_group_swap(srcs[1].clone(), FrameShape::Quad).into_iter();
-                vec![
-                    videos1,
-                    VideoList::from_videos(videos2.next().unwrap(), 1, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 2, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 3, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 4, sorter.clone()),
-                ]
-            }
-            (FrameShape::HorizEmph, 2) | (FrameShape::HorizEmph2, 2) => {
-                let mut videos1 = helper_functions::video_group_swap(srcs[0].clone(), FrameShape::Dual).into_iter();
-                let mut videos2 = helper_functions::video_group_swap(srcs[1].clone(), FrameShape::Dual).into_iter();
-                vec![
-                    VideoList::from_videos(videos1.next().unwrap(), 0, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 1, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 2, sorter.clone()),
-                    VideoList::from_videos(videos1.next().unwrap(), 3, sorter.clone()),
-                ]
-            }
-            (FrameShape::SideVert, 2) | (FrameShape::SideVert2, 2) => {
-                // vertical parts
-                let videos1 = VideoList::from_videos(helper_functions::scan_dir_for_videos(srcs[0].clone()), 0, sorter.clone());
-                // horizontal parts
-                let mut videos2 = helper_functions::video_group_swap(srcs[1].clone(), FrameShape::Dual).into_iter();
-                vec![
-                    videos1,
-                    VideoList::from_videos(videos2.next().unwrap(), 1, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 2, sorter.clone()),
-                ]
-            }
-            (FrameShape::CentreEmphVert, 3) | (FrameShape::CentreEmphVert2, 3) => {
-                // top horizontal group
-                let videos1 = VideoList::from_videos(helper_functions::scan_dir_for_videos(srcs[0].clone()), 0, sorter.clone());
-                // vertical group
-                let mut videos2 = helper_functions::video_group_swap(srcs[1].clone(), FrameShape::Dual).into_iter();
-                // bottom horizontal group
-                let mut videos3 = helper_functions::video_group_swap(srcs[2].clone(), FrameShape::Dual).into_iter();
-
-                vec![
-                    videos1,
-                    VideoList::from_videos(videos2.next().unwrap(), 1, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 2, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 3, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 4, sorter.clone()),
-                ]
-            }
-            (FrameShape::CentreEmphVert, 2) | (FrameShape::CentreEmphVert2, 2) => {
-                // vertical group
-                let mut videos2 = helper_functions::video_group_swap(srcs[0].clone(), FrameShape::Dual).into_iter();
-                // bottom horizontal group
-                let mut videos3 = helper_functions::video_group_swap(srcs[1].clone(), FrameShape::Triple).into_iter();
-
-                vec![
-                    VideoList::from_videos(videos3.next().unwrap(), 0, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 1, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 2, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 3, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 4, sorter.clone()),
-                ]
-            }
-            (FrameShape::MoreHoriz, 3) | (FrameShape::MoreHoriz2, 3) => {
-                // top horizontal group
-                let videos1 = VideoList::from_videos(helper_functions::scan_dir_for_videos(srcs[0].clone()), 0, sorter.clone());
-                // vertical group
-                let mut videos2 = helper_functions::video_group_swap(srcs[1].clone(), FrameShape::Dual).into_iter();
-                // bottom horizontal group
-                let mut videos3 = helper_functions::video_group_swap(srcs[2].clone(), FrameShape::Quad).into_iter();
-                vec![
-                    videos1,
-                    VideoList::from_videos(videos2.next().unwrap(), 1, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 2, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 3, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 4, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 5, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 6, sorter.clone()),
-                ]
-            }
-            (FrameShape::ExtendedLandscape, 2) => {
-                let videos0 = VideoList::from_videos(helper_functions::scan_dir_for_videos(srcs[0].clone()), 0, sorter.clone());
-                let mut videos1 = helper_functions::video_group_swap_n(srcs[1].clone(), 8).into_iter();
-
-                vec![
-                    videos0,
-                    VideoList::from_videos(videos1.next().unwrap(), 1, sorter.clone()),
-                    VideoList::from_videos(videos1.next().unwrap(), 2, sorter.clone()),
-                    VideoList::from_videos(videos1.next().unwrap(), 3, sorter.clone()),
-                    VideoList::from_videos(videos1.next().unwrap(), 4, sorter.clone()),
-                    VideoList::from_videos(videos1.next().unwrap(), 5, sorter.clone()),
-                    VideoList::from_videos(videos1.next().unwrap(), 6, sorter.clone()),
-                    VideoList::from_videos(videos1.next().unwrap(), 7, sorter.clone()),
-                    VideoList::from_videos(videos1.next().unwrap(), 8, sorter.clone()),
-                ]
-            }
-            (FrameShape::ExtendedLandscape, 3) => {
-                let videos1 = VideoList::from_videos(helper_functions::scan_dir_for_videos(srcs[0].clone()), 0, sorter.clone());
-                let mut videos2 = helper_functions::video_group_swap_n(srcs[1].clone(), 2).into_iter();
-                let mut videos3 = helper_functions::video_group_swap_n(srcs[2].clone(), 6).into_iter();
-
-                vec![
-                    videos1,
-                    VideoList::from_videos(videos2.next().unwrap(), 1, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 2, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 3, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 4, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 5, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 6, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 7, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 8, sorter.clone()),
-                ]
-            }
-            (FrameShape::ExtendedLandscape, 4) => {
-                let videos0 = VideoList::from_videos(helper_functions::scan_dir_for_videos(srcs[0].clone()), 0, sorter.clone());
-                let mut videos1 = helper_functions::video_group_swap(srcs[1].clone(), FrameShape::Dual).into_iter();
-                let mut videos2 = helper_functions::video_group_swap(srcs[2].clone(), FrameShape::Triple).into_iter();
-                let mut videos3 = helper_functions::video_group_swap(srcs[3].clone(), FrameShape::Triple).into_iter();
-
-                vec![
-                    videos0,
-                    VideoList::from_videos(videos1.next().unwrap(), 1, sorter.clone()),
-                    VideoList::from_videos(videos1.next().unwrap(), 2, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 3, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 4, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 5, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 6, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 7, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 8, sorter.clone()),
-                ]
-            }
-            (FrameShape::ExtendedLandscape2, 2) => {
-                let videos1 = VideoList::from_videos(helper_functions::scan_dir_for_videos(srcs[0].clone()), 0, sorter.clone());
-                let mut videos2 = helper_functions::video_group_swap_n(srcs[1].clone(), 7).into_iter();
-
-                vec![
-                    videos1,
-                    VideoList::from_videos(videos2.next().unwrap(), 1, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 2, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 3, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 4, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 5, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 6, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 7, sorter.clone()),
-                ]
-            }
-            (FrameShape::ExtendedLandscape2, 3) => {
-                let videos1 = VideoList::from_videos(helper_functions::scan_dir_for_videos(srcs[0].clone()), 0, sorter.clone());
-                let mut videos2 = helper_functions::video_group_swap_n(srcs[1].clone(), 3).into_iter();
-                let mut videos3 = helper_functions::video_group_swap_n(srcs[2].clone(), 4).into_iter();
-                vec![
-                    videos1,
-                    VideoList::from_videos(videos2.next().unwrap(), 1, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 2, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 3, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 4, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 5, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 6, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 7, sorter.clone()),
-                ]
-            }
+        let bindings = screens.bind_folders(srcs).unwrap_or_else(|e| panic!("{}", e));
+        Self::new_from_bindings(bindings, src_out, screens, sorter)
+    }
 
-            (FrameShape::OffsetVH4x4, 2) => {
-                let mut videos2 = helper_functions::video_group_swap_n(srcs[0].clone(), 4).into_iter();
-                let mut videos3 = helper_functions::video_group_swap_n(srcs[1].clone(), 4).into_iter();
-                vec![
-                    VideoList::from_videos(videos2.next().unwrap(), 0, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 1, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 2, sorter.clone()),
-                    VideoList::from_videos(videos2.next().unwrap(), 3, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 4, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 5, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 6, sorter.clone()),
-                    VideoList::from_videos(videos3.next().unwrap(), 7, sorter.clone()), ]
-            }
-            (_, _) => {
-                srcs.into_iter()
-                    .enumerate()
-                    .map(|(i, x)| VideoList::from_videos(helper_functions::scan_dir_for_videos(x), i as u32, sorter.clone()))
-                    .collect()
+    /// tiles filled with exactly the clips given, in the order given
+    pub fn new_from_plan(
+        tiles: Vec<Vec<Entry>>,
+        src_out: impl Into<PathBuf>,
+        screens: FrameShape,
+    ) -> VideoGroup {
+        VideoGroup {
+            videos: tiles
+                .into_iter()
+                .enumerate()
+                .map(|(i, clips)| {
+                    let videos = clips.into_iter().map(|(src, trim)| Video::from_path(src).with_trim(trim)).collect();
+                    VideoList::from_videos(videos, i as u32, SortOrder::Nan)
+                })
+                .collect(),
+            output_target: src_out.into(),
+            video_sizer: VideoEditData::init(),
+            slots: (0..screens.count() as usize).collect(),
+            shape_style: screens,
+            on_exhaust: OnExhaust::Stop,
+            frame_limit: None,
+            audio_focus: AudioFocus::All,
+            audio_overrides: AudioOverrides::default(),
+            audio_tracks: AudioTracks::Mix,
+            export_edl: false,
+            frames_written: 0,
+            chapter_hero: 0,
+        }
+    }
+
+    /// each binding's folder is split across its tile positions, balanced by video length
+    pub fn new_from_bindings(
+        bindings: Vec<Binding>,
+        src_out: impl Into<PathBuf>,
+        screens: FrameShape,
+        sorter: SortOrder,
+    ) -> VideoGroup {
+        let mut vid_lists: Vec<Option<VideoList>> = (0..screens.count()).map(|_| None).collect();
+        for (positions, src) in bindings {
+            let videos = match positions.len() {
+                1 => vec![helper_functions::scan_dir_for_videos(src)],
+                2 => helper_functions::video_group_swap(src, FrameShape::Dual),
+                3 => helper_functions::video_group_swap(src, FrameShape::Triple),
+                4 => helper_functions::video_group_swap(src, FrameShape::Quad),
+                n => helper_functions::video_group_swap_n(src, n),
+            };
+            for (pos, vids) in positions.into_iter().zip(videos) {
+                vid_lists[pos] = Some(VideoList::from_videos(vids, pos as u32, sorter.clone()));
             }
-        };
+        }
         VideoGroup {
-            videos: vid_lists,
+            videos: vid_lists.into_iter().map(|v| v.expect("every tile must be bound to a folder")).collect(),
             output_target: src_out.into(),
             video_sizer: VideoEditData::init(),
+            slots: (0..screens.count() as usize).collect(),
             shape_style: screens,
+            on_exhaust: OnExhaust::Stop,
+            frame_limit: None,
+            audio_focus: AudioFocus::All,
+            audio_overrides: AudioOverrides::default(),
+            audio_tracks: AudioTracks::Mix,
+            export_edl: false,
+            frames_written: 0,
+            chapter_hero: 0,
+        }
+    }
+
+    pub(crate) fn set_on_exhaust(&mut self, on_exhaust: OnExhaust) {
+        self.on_exhaust = on_exhaust;
+    }
+
+    pub(crate) fn set_audio_focus(&mut self, audio_focus: AudioFocus) {
+        self.audio_focus = audio_focus;
+    }
+
+    /// per group mute, gain and pan for the audio mix
+    pub(crate) fn set_audio_overrides(&mut self, audio_overrides: AudioOverrides) {
+        self.audio_overrides = audio_overrides;
+    }
+
+    pub(crate) fn set_audio_tracks(&mut self, audio_tracks: AudioTracks) {
+        self.audio_tracks = audio_tracks;
+    }
+
+    pub(crate) fn set_export_edl(&mut self, export_edl: bool) {
+        self.export_edl = export_edl;
+    }
+
+    /// keep only the videos needed for each group to reach `seconds`, the output is then cut
+    /// to exactly that length which trims the last video of every group.
+    /// must be called after [VideoGroup::set_video_sizer] so the fps is known
+    pub(crate) fn set_duration(&mut self, seconds: f64) {
+        for vl in self.videos.iter_mut() {
+            let videos: Vec<Video> = vl.videos.drain(..).collect();
+            let count = videos.len();
+            vl.videos.extend(group_splitter::select_for_duration(videos, (seconds * 1000.0) as i64));
+            println!("Duration: group {} uses {} of {} videos", vl.position + 1, vl.videos.len(), count);
         }
+        self.limit_duration(seconds);
+    }
+
+    /// cut the output to exactly `seconds`, the videos of each group are kept as they are
+    pub(crate) fn limit_duration(&mut self, seconds: f64) {
+        self.frame_limit = Some((seconds * self.video_sizer.fps as f64).round() as u64);
+    }
+
+    /// the clips of each tile in play order, see [crate::plan::Plan]
+    pub(crate) fn plan_tiles(&self) -> Vec<Vec<Entry>> {
+        self.videos.iter()
+            .map(|vl| vl.videos.iter().map(|v| (v.src.clone(), v.trim)).collect())
+            .collect()
     }
 
     pub fn set_video_sizer(&mut self, video_sizer: VideoEditData) {
@@ -890,7 +814,46 @@ impl VideoGroup {
         }
     }
 
-    pub fn main_loop(&mut self, drop_audio: bool, encoder_args: Vec<String>) {
+    /// write the composite frame shown `time_secs` into the output to a png,
+    /// only the clips on screen at that time are decoded
+    pub fn render_preview(&mut self, time_secs: f64, out: &PathBuf) -> Result<(), String> {
+        let time_millis = (time_secs * 1000.0) as i64;
+        let mut frames = vec![];
+        for vl in self.videos.iter_mut() {
+            match vl.frame_at(time_millis) {
+                Some(frame) => frames.push(frame),
+                None => return Err(format!(
+                    "tile {} has no video at {}, try an earlier time (see --length)",
+                    vl.position + 1, seconds_to_hhmmss(time_secs as u64)
+                )),
+            }
+        }
+        let mut canvas = vec![0u8; (self.video_sizer.output_width * self.video_sizer.output_height * 3) as usize];
+        self.shape_style.frame_joiner(&frames, &self.video_sizer, &mut canvas, &BlitPool::new());
+
+        let mut png = FfmpegCommand::new();
+        png.args([
+            "-loglevel", "error",
+            "-f", "rawvideo",
+            "-pix_fmt", "rgb24",
+            "-s", &format!("{}x{}", self.video_sizer.output_width, self.video_sizer.output_height),
+        ])
+            .input("pipe:0")
+            .args(["-frames:v", "1", "-y"])
+            .output(out.to_str().unwrap());
+        let mut png = png.spawn().map_err(|e| format!("failed to start ffmpeg: {}", e))?;
+        let mut stdin = png.take_stdin().unwrap();
+        stdin.write_all(&canvas).map_err(|e| format!("failed to write preview: {}", e))?;
+        // closing stdin lets ffmpeg finish the file
+        drop(stdin);
+        iter_ffmpeg_events(&mut png);
+        match png.wait() {
+            Ok(status) if status.success() => Ok(()),
+            _ => Err(format!("ffmpeg failed to write {:?}", out)),
+        }
+    }
+
+    pub fn main_loop(&mut self, audio: AudioMode, encoder_args: Vec<String>) -> Result<(), EncoderError> {
         let temp_folder = std::env::current_dir().unwrap().join("TempFolder");
 
         println!("TempFolder: {:?}", temp_folder);
@@ -901,21 +864,26 @@ impl VideoGroup {
 
         let encoder_args: Vec<&str> = encoder_args.iter().map(|s| s.as_str()).collect();
         // Main loop **Video**
-        let temp_file = self.main_loop_video(encoder_args.as_slice());
-
-        if drop_audio {
-            for _ in 0..3 {
-                match std::fs::rename(temp_file.clone(), self.output_target.clone()) {
-                    Ok(_) => { break }
-                    Err(e) => {
-                        println!("Sleeping due to Error:{:?}", e);
-                        std::thread::sleep(std::time::Duration::from_secs_f32(0.2))
+        let temp_file = self.main_loop_video(encoder_args.as_slice(), audio == AudioMode::Live);
+
+        match &temp_file {
+            Err(_) => eprintln!("Video export failed, audio is skipped"),
+            Ok(temp_file) if audio != AudioMode::TwoPass => {
+                for _ in 0..3 {
+                    match std::fs::rename(temp_file, self.output_target.clone()) {
+                        Ok(_) => { break }
+                        Err(e) => {
+                            println!("Sleeping due to Error:{:?}", e);
+                            std::thread::sleep(std::time::Duration::from_secs_f32(0.2))
+                        }
                     }
                 }
             }
-        } else {
             // Main loop **Audio**
-            self.main_loop_audio(&temp_file, &temp_folder);
+            Ok(temp_file) => self.main_loop_audio(temp_file, &temp_folder),
+        }
+        if temp_file.is_ok() {
+            self.write_timeline(&temp_folder);
         }
         #[cfg(not(feature = "keepTempFiles"))]
         match std::fs::remove_dir_all(temp_folder) {
@@ -924,10 +892,11 @@ impl VideoGroup {
                 eprintln!("Removing temp_dir failed Err: {:?}", e)
             }
         };
+        temp_file.map(|_| ())
     }
 
     //noinspection SpellCheckingInspection
-    fn main_loop_video(&mut self, encoder_args: &[&str]) -> PathBuf {
+    fn main_loop_video(&mut self, encoder_args: &[&str], live_audio: bool) -> Result<PathBuf, EncoderError> {
         #[cfg(feature = "hyperDebug")]
         helper_functions::parse_debug("main_loop", file!(), line!());
         // frame timer for simple speed testing:
@@ -960,7 +929,32 @@ impl VideoGroup {
                 "-r",
                 &format!("{}", self.video_sizer.fps),
             ])
-            .input("pipe:0")
+            .input("pipe:0");
+        // the focus only changes the mix, stems are sent again on their own inputs before it is applied
+        let separate_stems = self.audio_tracks == AudioTracks::Both && self.audio_focus != AudioFocus::All;
+        // every group's audio is read alongside the video and mixed by the encoder
+        let audio_inputs = match live_audio {
+            true => {
+                let groups = self.videos.len();
+                let count = if separate_stems { groups * 2 } else { groups };
+                let inputs = AudioInputs::bind(count)
+                    .and_then(|inputs| inputs.add_inputs(&mut out_target).map(|_| inputs))
+                    .map_err(|e| EncoderError::Failed(None, vec![format!("audio inputs: {}", e)]))?;
+                if self.audio_tracks.mix() {
+                    out_target.args(["-filter_complex", &self.shape_style.audio_args_with_vid(&self.audio_overrides)]);
+                }
+                let first_stem = if separate_stems { groups + 1 } else { 1 };
+                out_target
+                    .args(["-map", "0:v:0"])
+                    .args(audio_track_args(self.audio_tracks, groups, first_stem));
+                for vl in self.videos.iter_mut() {
+                    vl.live_audio = true;
+                }
+                Some(inputs)
+            }
+            false => None,
+        };
+        out_target
             .args(["-y"])
             .args(encoder_args)
             // .arg(ENCODER_ARGS.get().unwrap())
@@ -970,10 +964,17 @@ impl VideoGroup {
         println!("FFMPEG ouput Command:");
         out_target.print_command();
 
-        let mut out_target = out_target.spawn().unwrap();
-        let mut stdin = out_target.take_stdin().unwrap();
+        let encoder = EncoderSink::spawn(
+            out_target,
+            temp_out_file.clone(),
+            (self.video_sizer.output_width * self.video_sizer.output_height * 3) as usize,
+            audio_inputs,
+        )?;
+        let blit_pool = BlitPool::new();
 
         let mut frame_counter = 0u64;
+        // frames handed to the encoder, a frame whose audio could not be sent was still written
+        let mut frames_written = 0u64;
         // main loop
         let init_time = Instant::now();
 
@@ -990,9 +991,22 @@ impl VideoGroup {
         let fps_u64 = self.video_sizer.fps.clone() as u64;
         let frames_between_update = fps_u64 * 30;
         let f64_frames_between_update = frames_between_update as f64;
+        // groups still shown and their latest frames, see [VideoGroup::next_frames]
+        let mut active: Vec<usize> = (0..self.videos.len()).collect();
+        let mut frame_prep: Vec<OutputVideoFrame> = Vec::with_capacity(self.videos.len());
+        let mut exhausted = vec![false; self.videos.len()];
+        // canvases to clear before they are joined into, see [VideoGroup::reflow]
+        let mut stale_canvases = 0;
+        let hero = self.hero();
+        self.chapter_hero = hero;
+        let focus_mode = if self.audio_tracks.mix() { self.audio_focus.clone() } else { AudioFocus::All };
+        let mut focus = Focus::new(focus_mode, self.videos.len(), hero);
         'mainloop: loop {
             // print data about video export
             frame_counter += 1;
+            if self.frame_limit.is_some_and(|limit| frame_counter > limit) {
+                break 'mainloop;
+            }
             if frame_counter.rem(frames_between_update) == 0 {
                 (t_last, t_now) = (t_now, Instant::now());
                 let fps = f64_frames_between_update / (t_now - t_last).as_secs_f64();
@@ -1006,22 +1020,9 @@ impl VideoGroup {
             // collect set of frames to process
             #[cfg(feature = "hyperDebug")]
             helper_functions::parse_debug("Frame Prep Started for frame", file!(), line!());
-            let frame_prep: Vec<Option<OutputVideoFrame>> = self
-                .videos
-                .iter_mut()
-                .map(|x| x.next_frame(&frame_counter))
-                .collect();
-
             // if no more frames are available break the loop
-            if frame_prep.iter().any(|f| f.is_none()) {
-                for (fp, vl) in frame_prep.iter().zip(self.videos.iter()) {
-                    match fp {
-                        None => {
-                            println!("video: {}", vl.complete_videos.iter().last().unwrap().src.to_str().unwrap());
-                        }
-                        Some(_) => {}
-                    }
-                }
+            let tiles = active.len();
+            if self.next_frames(&frame_counter, &mut active, &mut frame_prep, &mut exhausted).not() {
                 #[cfg(feature = "hyperDebug")]
                 helper_functions::parse_debug(
                     "Frame prep returned a none value, mainloop ended",
@@ -1029,40 +1030,28 @@ impl VideoGroup {
                 break 'mainloop;
             }
 
-            // unwrap frames into frame joiner format
-            let frame_prep: Vec<OutputVideoFrame> =
-                frame_prep.into_iter().map(|x1| x1.unwrap()).collect();
-
             // join frames from separate items using `FrameShape` 
-            let frames = self.shape_style.frame_joiner(frame_prep, &self.video_sizer);
-            #[cfg(feature = "hyperDebug")]
-            println!("Read: {} bytes", frames.len());
-
+            // the encoder has stopped if no canvas is returned, `finish` below reports why
+            let Some(mut canvas) = encoder.canvas() else {
+                break 'mainloop;
+            };
+            // after a reflow every canvas still holds the old layout where the new one has no tile
+            if active.len() != tiles {
+                stale_canvases = CANVAS_COUNT;
+            }
+            if stale_canvases > 0 {
+                canvas.fill(0);
+                stale_canvases -= 1;
+            }
+            self.shape_style.frame_joiner(&frame_prep, &self.video_sizer, &mut canvas, &blit_pool);
             #[cfg(feature = "hyperDebug")]
-            helper_functions::parse_debug("main_loop: frame_write", file!(), line!());
-            // write to the buffer
-            let written = stdin.write(&frames);
-            // check that write worked
-            match written {
-                Ok(_c) => {
-                    #[cfg(feature = "hyperDebug")]
-                    helper_functions::parse_debug("main_loop: frames_joined", file!(), line!());
-                    #[cfg(feature = "hyperDebug")]
-                    println!("{} bytes written to Ffmpeg", _c);
-                }
-                Err(x) => {
-                    #[cfg(feature = "hyperDebug")]
-                    helper_functions::parse_debug(
-                        "main_loop: frames_joined_failure",
-                        file!(),
-                        line!(),
-                    );
-                    eprintln!("main_loop: frames_joined_failure {:?}", x);
-                    println!("ErrorKind = {:?}", x.kind());
-                    println!("HELP: \n\tThis is usually because of an incorrectly configured video encoder. (see settings.ini)");
-                    println!("\tIt could also be becasue ffmpeg cannot access the output file specified");
-                    panic!("Un recoverable error in ffmpeg pipe")
-                }
+            println!("Read: {} bytes", canvas.len());
+            if encoder.write(canvas).not() {
+                break 'mainloop;
+            }
+            frames_written += 1;
+            if live_audio && self.write_audio(&encoder, &mut focus, frame_counter, &active, &exhausted, separate_stems).not() {
+                break 'mainloop;
             }
             #[cfg(feature = "hyperDebug")]
             helper_functions::parse_debug("main_loop: frames_written", file!(), line!());
@@ -1077,13 +1066,197 @@ impl VideoGroup {
             frame_counter, hour, min, fps
         );
         println!("End of mainloop");
+        for vl in self.videos.iter_mut() {
+            vl.stop_decoders();
+        }
+
+        self.frames_written = frames_written;
 
-        let _ = out_target.kill();
-        println!("kill command sent");
-        out_target.wait().expect("Failed to wait on child");
+        // closing the pipe lets ffmpeg flush and finalise the file
+        if let Err(e) = encoder.finish() {
+            let _ = std::fs::remove_file(&temp_out_file);
+            return Err(e);
+        }
         println!("wait complete");
+        if live_audio {
+            for (i, vl) in self.videos.iter().enumerate() {
+                report_drift(i, vl.samples_sent, self.frames_written, self.video_sizer.fps, SAMPLE_RATE);
+            }
+        }
+
+        Ok(temp_out_file)
+    }
+
+    /// the group shown in the largest tile
+    fn hero(&self) -> usize {
+        let slot = self.video_sizer.rects.iter()
+            .take(self.slots.len())
+            .enumerate()
+            .fold((0, 0), |best, (i, r)| if r.w * r.h > best.1 { (i, r.w * r.h) } else { best })
+            .0;
+        self.slots[slot]
+    }
+
+    /// write `<output>.timeline.json` with every clip placement, a CMX3600 edl per tile with `--edl`,
+    /// and add a chapter for each clip of the hero tile to the output. failures are only reported,
+    /// the output itself is already complete
+    fn write_timeline(&self, temp_folder: &Path) {
+        let fps = self.video_sizer.fps;
+        let tiles: Vec<Vec<Span>> = self.videos.iter()
+            .map(|vl| clamp(&vl.timeline(), self.frames_written))
+            .collect();
+
+        let json = self.output_target.with_extension("timeline.json");
+        match std::fs::write(&json, timeline_json(&tiles, self.frames_written, fps)) {
+            Ok(_) => println!("Timeline: {:?}", json),
+            Err(e) => eprintln!("WARNING timeline not written to {:?}: {}", json, e),
+        }
+        if self.export_edl {
+            let name = self.output_target.file_stem().unwrap_or_default().to_string_lossy();
+            for (i, spans) in tiles.iter().enumerate() {
+                let path = self.output_target.with_extension(format!("tile{}.edl", i + 1));
+                if let Err(e) = std::fs::write(&path, edl(&format!("{} tile {}", name, i + 1), spans, fps)) {
+                    eprintln!("WARNING edl not written to {:?}: {}", path, e);
+                }
+            }
+        }
+
+        // a single clip needs no chapters, the chapters follow the hero the output started with
+        let hero = &tiles[self.chapter_hero];
+        if hero.len() > 1 {
+            let metadata = temp_folder.join("chapters.txt");
+            let embedded = std::fs::write(&metadata, ffmetadata(hero, fps))
+                .map_err(|e| e.to_string())
+                .and_then(|_| embed_chapters(&self.output_target, &metadata));
+            match embedded {
+                Ok(_) => println!("Chapters: {} added for tile {}", hero.len(), self.chapter_hero + 1),
+                Err(e) => eprintln!("WARNING chapters not added: {}", e),
+            }
+        }
+    }
+
+    /// fetch the next frame of every active group into `frames`, groups that have run out of
+    /// videos are handled by the `--on-exhaust` policy. returns false when the output should end
+    fn next_frames(&mut self, fc: &u64, active: &mut Vec<usize>,
+                   frames: &mut Vec<OutputVideoFrame>, exhausted: &mut [bool]) -> bool {
+        let first = frames.is_empty();
+        let mut slot = 0;
+        while slot < active.len() {
+            let i = active[slot];
+            // held and coloured tiles keep their last frame
+            if exhausted[i] && self.on_exhaust != OnExhaust::Loop {
+                slot += 1;
+                continue;
+            }
+            let frame = match self.videos[i].next_frame(fc) {
+                Some(f) => Some(f),
+                None => {
+                    if let Some(v) = self.videos[i].complete_videos.back() {
+                        println!("video: {}", v.src.to_str().unwrap());
+                    }
+                    exhausted[i] = true;
+                    match &self.on_exhaust {
+                        // groups without a first frame can not be held or coloured
+                        _ if first => return false,
+                        OnExhaust::Stop => return false,
+                        OnExhaust::Hold => None,
+                        OnExhaust::Black(rgb) => {
+                            let r = self.video_sizer.rects[slot];
+                            Some(OutputVideoFrame {
+                                width: r.w,
+                                height: r.h,
+                                pix_fmt: "rgb24".to_string(),
+                                output_index: 0,
+                                data: rgb.repeat((r.w * r.h) as usize),
+                                frame_num: 0,
+                                timestamp: 0.0,
+                            })
+                        }
+                        OnExhaust::Loop => {
+                            println!("Looping group {}", i + 1);
+                            if self.videos[i].restart().not() {
+                                return false;
+                            }
+                            match self.videos[i].next_frame(fc) {
+                                Some(f) => Some(f),
+                                None => return false,
+                            }
+                        }
+                        // every tile is fetched again at its new size
+                        OnExhaust::Reflow => {
+                            if !self.reflow_out(slot, active, frames) {
+                                return false;
+                            }
+                            slot = 0;
+                            continue;
+                        }
+                    }
+                }
+            };
+            match frame {
+                Some(f) if slot == frames.len() => frames.push(f),
+                // the replaced frame's buffer is reused by the decoder
+                Some(f) => {
+                    let old = std::mem::replace(&mut frames[slot], f);
+                    self.videos[i].recycle(old);
+                }
+                None => {}
+            }
+            slot += 1;
+        }
+        // looping groups run until every group has played through at least once
+        exhausted.iter().all(|e| *e).not()
+    }
 
-        temp_out_file
+    /// send the audio of frame `fc` for every group to the encoder, groups that are not
+    /// playing (held, coloured or reflowed out) are silent. `separate_stems` also sends each
+    /// group's audio before the focus is applied, to the inputs after the mix's
+    fn write_audio(&mut self, encoder: &EncoderSink, focus: &mut Focus, fc: u64,
+                   active: &[usize], exhausted: &[bool], separate_stems: bool) -> bool {
+        let samples = samples_in_frame(fc, self.video_sizer.fps);
+        let mut pcm: Vec<Vec<u8>> = self.videos.iter_mut().enumerate().map(|(i, vl)| {
+            let playing = active.contains(&i) && (exhausted[i].not() || self.on_exhaust == OnExhaust::Loop);
+            let mut pcm = Vec::with_capacity(samples * SAMPLE_BYTES);
+            match playing {
+                true => vl.next_audio(samples, &mut pcm),
+                false => pcm.resize(samples * SAMPLE_BYTES, 0),
+            }
+            vl.samples_sent += (pcm.len() / SAMPLE_BYTES) as u64;
+            pcm
+        }).collect();
+        let groups = pcm.len();
+        if separate_stems && pcm.iter().enumerate().all(|(i, pcm)| encoder.write_audio(groups + i, pcm.clone())).not() {
+            return false;
+        }
+        focus.apply(fc, self.video_sizer.fps, &mut pcm);
+        pcm.into_iter().enumerate().all(|(i, pcm)| encoder.write_audio(i, pcm))
+    }
+
+    /// take the tile in `slot` out of the output and reflow the rest. the tiles before it were
+    /// already decoded at the old size for this frame, they are rewound and `frames` is emptied
+    /// so every tile is fetched again. returns false if no tiles are left
+    fn reflow_out(&mut self, slot: usize, active: &mut Vec<usize>, frames: &mut Vec<OutputVideoFrame>) -> bool {
+        active.remove(slot);
+        for i in active[..slot].iter() {
+            self.videos[*i].rewind();
+        }
+        frames.clear();
+        self.reflow(active)
+    }
+
+    /// switch to a shape with one tile per `active` group, returns false if none are left
+    fn reflow(&mut self, active: &[usize]) -> bool {
+        let shape = match FrameShape::with_count(active.len() as u32) {
+            Some(shape) => shape,
+            None => return false,
+        };
+        println!("Reflowing to {} tiles", active.len());
+        self.video_sizer.set_shape(shape);
+        self.slots = active.to_vec();
+        for (slot, i) in active.iter().enumerate() {
+            self.videos[*i].reshape(slot as u32, self.video_sizer.clone());
+        }
+        true
     }
 
     fn main_loop_audio(&mut self, temp_out_file: &PathBuf, temp_folder: &PathBuf) {
@@ -1098,7 +1271,7 @@ impl VideoGroup {
 
         let audio_segments: Vec<Vec<PathBuf>> = self.videos.iter_mut()
             .enumerate()
-            .map(|(i, v)| v.cheap_audio_exporter_out_proc(i, &temp_folder))
+            .map(|(i, v)| v.cheap_audio_exporter_out_proc(i, temp_folder, self.frames_written))
             .collect();
 
         println!("Audio segments exported");
@@ -1108,6 +1281,8 @@ impl VideoGroup {
             &temp_out_file,
             self.output_target.clone(),
             &self.shape_style,
+            &self.audio_overrides,
+            self.audio_tracks,
         );
 
         println!("Audio Complete");
@@ -1118,8 +1293,72 @@ impl VideoGroup {
 }
 
 
+/// audio samples covering frame `fc` (counted from 1), frames alternate between the rounded
+/// down and rounded up count so the audio never drifts from the video
+fn samples_in_frame(fc: u64, fps: f32) -> usize {
+    (sample_at(fc, fps, SAMPLE_RATE) - sample_at(fc - 1, fps, SAMPLE_RATE)) as usize
+}
+
+//noinspection SpellCheckingInspection
+/// export exactly `samples` samples of `src`'s audio as a wav, padded with silence if the clip's
+/// audio is shorter. None, or a clip without audio, exports generated silence
+fn audio_export_proc_out(src: Option<&PathBuf>, source_in: f64, samples: u64, out: &Path) -> FfmpegChild {
+    let rate = EXPORT_SAMPLE_RATE.to_string();
+
+    let mut ffm = FfmpegCommand::new();
+    match src {
+        Some(src) => {
+            if source_in > 0.0 {
+                ffm.seek(format!("{:.3}", source_in));
+            }
+            ffm.input(src.to_str().unwrap()).no_video();
+            ffm.filter(export_filter(samples, EXPORT_SAMPLE_RATE));
+        }
+        None => {
+            ffm.format("lavfi")
+                .input(format!("anullsrc=r={}:cl=stereo", rate));
+            ffm.filter(format!("atrim=end_sample={}", samples));
+        }
+    }
+    ffm.args([
+            "-ar", &rate,
+            // every export has the same layout, the concat demuxer needs matching streams
+            "-ac", "2",
+        ])
+        .arg("-y")
+        .output(out.to_str().unwrap());
+
+    ffm.spawn().unwrap()
+}
+
+/// pad or cut a clip's audio to exactly `samples` samples at `rate`, the clip is resampled first
+/// as `apad` and `atrim` count samples at the rate of their input
+fn export_filter(samples: u64, rate: u32) -> String {
+    format!("aresample={},apad=whole_len={},atrim=end_sample={}", rate, samples, samples)
+}
+
+/// samples in the first audio stream of a wav
+fn wav_samples(wav: &Path) -> Option<u64> {
+    let out = std::process::Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "a:0", "-show_entries", "stream=duration_ts", "-of", "json"])
+        .arg(wav)
+        .output()
+        .ok()?;
+    let probe: serde_json::Value = serde_json::from_slice(&out.stdout).ok()?;
+    probe.pointer("/streams/0/duration_ts")?.as_u64()
+}
+
+/// print how far a group's audio is from the video, tiles are counted from 1
+fn report_drift(grp: usize, samples: u64, total_frames: u64, fps: f32, rate: u32) {
+    let drift = drift_millis(samples, total_frames, fps, rate);
+    match drift.abs() < 1000.0 / fps as f64 {
+        true => println!("Audio drift tile {}: {:+.1}ms", grp + 1, drift),
+        false => eprintln!("WARNING Audio drift tile {}: {:+.1}ms, more than a frame", grp + 1, drift),
+    }
+}
+
 pub(crate) trait Joiner {
-    fn frame_joiner(&self, frames: Vec<OutputVideoFrame>, out_sh: &VideoEditData) -> Vec<u8>;
+    fn frame_joiner(&self, frames: &[OutputVideoFrame], out_sh: &VideoEditData, out: &mut [u8], pool: &BlitPool);
 }
 
 impl Debug for Video {
@@ -1146,3 +1385,85 @@ impl Debug for VideoGroup {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a group of `tiles` single clip tiles laid out as `shape` in a 90x30 output, nothing is decoded
+    fn test_group(tiles: usize, shape: FrameShape) -> VideoGroup {
+        let plan = (0..tiles).map(|i| vec![(PathBuf::from(format!("g{}.mp4", i)), Trim::default())]).collect();
+        let mut group = VideoGroup::new_from_plan(plan, "out.mp4", shape.clone());
+        let mut sizer = VideoEditData::init_wxh(90, 30, tiles as u32);
+        sizer.set_shape(shape);
+        group.set_video_sizer(sizer);
+        group
+    }
+
+    #[test]
+    fn hero_follows_reflow() {
+        let mut group = test_group(3, FrameShape::Triple);
+        assert_eq!(group.hero(), 0);
+        // group 0 ran out, the largest tile of the new shape now shows group 1
+        assert!(group.reflow(&[1, 2]));
+        assert_eq!(group.video_sizer.rects, FrameShape::Dual.tile_rects(90, 30));
+        assert_eq!(group.hero(), 1);
+    }
+
+    #[test]
+    fn reflow_fetches_every_tile_again() {
+        let mut group = test_group(3, FrameShape::Triple);
+        for vl in group.videos.iter_mut() {
+            vl.videos[0].frame_count = 4;
+        }
+        // tile 0 has its 4th frame for this output frame at the old size, then group 1 runs out
+        let frame = OutputVideoFrame {
+            width: 30,
+            height: 30,
+            pix_fmt: "rgb24".to_string(),
+            output_index: 0,
+            data: vec![0; 30 * 30 * 3],
+            frame_num: 0,
+            timestamp: 0.0,
+        };
+        let (mut active, mut frames) = (vec![0, 1, 2], vec![frame]);
+        assert!(group.reflow_out(1, &mut active, &mut frames));
+        assert_eq!(active, vec![0, 2]);
+        assert!(frames.is_empty());
+        // group 0 decodes its 4th frame again at the new size, group 2 had not reached it yet
+        assert_eq!(group.videos[0].videos[0].frame_count, 3);
+        assert_eq!(group.videos[0].videos[0].resume_at, Some(3.0 / 30.0));
+        assert_eq!(group.videos[2].videos[0].resume_at, Some(4.0 / 30.0));
+        assert_eq!(group.slots, vec![0, 2]);
+
+        assert!(group.reflow_out(0, &mut active, &mut frames));
+        assert!(group.reflow_out(0, &mut active, &mut frames).not());
+    }
+
+    #[test]
+    fn prefetch_near_clip_end() {
+        let mut v = Video::from_path("a.mp4");
+        v.set_video_shape((30, 30), 30.0, FitMode::Crop);
+        assert!(v.near_end());
+        v.length_millis = Some(10_000);
+        assert!(v.near_end().not());
+        v.frame_count = 239;
+        assert!(v.near_end().not());
+        v.frame_count = 240;
+        assert!(v.near_end());
+    }
+
+    #[test]
+    fn export_resamples_before_trim() {
+        assert_eq!(export_filter(44100, 44100), "aresample=44100,apad=whole_len=44100,atrim=end_sample=44100");
+    }
+
+    #[test]
+    fn audio_samples_follow_frames() {
+        assert_eq!(samples_in_frame(1, 30.0), 1600);
+        // 29.97 fps does not divide evenly, the total still matches the video length
+        let total: usize = (1..=30_000).map(|fc| samples_in_frame(fc, 29.97)).sum();
+        assert_eq!(total, (30_000.0 * 48_000.0 / 29.97f32 as f64).floor() as usize);
+        assert!((1..=100).all(|fc| (1601..=1602).contains(&samples_in_frame(fc, 29.97))));
+    }
+}